@@ -0,0 +1,76 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use convolution::batched_convolver::BatchedConvolver;
+use convolution::multi_channel_convolver::MultiChannelConvolver;
+use convolution::Sample;
+
+const SAMPLE_RATE: usize = 48000;
+const SECONDS: usize = 5;
+
+fn time(
+    blocks: usize,
+    input: &[Vec<Sample>],
+    output: &mut [Vec<Sample>],
+    mut process: impl FnMut(&[Vec<Sample>], &mut [Vec<Sample>]),
+) -> Duration {
+    let start = Instant::now();
+    for _ in 0..blocks {
+        process(black_box(input), output);
+        black_box(&output);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    println!(
+        "{:>9} {:>10} {:>8} {:>14} {:>14}",
+        "channels", "response", "block", "per channel", "batched"
+    );
+    for channel_count in [2, 8, 16] {
+        for response_len in [4096, 48000] {
+            let responses: Vec<Vec<Sample>> = (0..channel_count)
+                .map(|channel| {
+                    (0..response_len)
+                        .map(|i| {
+                            let decay = (-(i as Sample) / 8000.0).exp();
+                            decay * (((i + channel) % 17) as Sample - 8.0) / 8.0
+                        })
+                        .collect()
+                })
+                .collect();
+            let responses: Vec<&[Sample]> = responses.iter().map(|x| x.as_slice()).collect();
+            for block_size in [128, 512] {
+                let mut multi_channel =
+                    MultiChannelConvolver::with_responses(&responses, block_size, response_len);
+                let mut batched =
+                    BatchedConvolver::with_responses(&responses, block_size, response_len);
+
+                let input: Vec<Vec<Sample>> = (0..channel_count)
+                    .map(|channel| {
+                        (0..block_size)
+                            .map(|i| (((i + channel) * 7919) % 201) as Sample / 100.0 - 1.0)
+                            .collect()
+                    })
+                    .collect();
+                let mut output = vec![vec![0.0; block_size]; channel_count];
+                let blocks = SECONDS * SAMPLE_RATE / block_size;
+
+                let per_channel = time(blocks, &input, &mut output, |input, output| {
+                    multi_channel.process(input, output)
+                });
+                let batched = time(blocks, &input, &mut output, |input, output| {
+                    batched.process(input, output)
+                });
+                println!(
+                    "{:>9} {:>10} {:>8} {:>12.1}ms {:>12.1}ms",
+                    channel_count,
+                    response_len,
+                    block_size,
+                    per_channel.as_secs_f64() * 1000.0,
+                    batched.as_secs_f64() * 1000.0
+                );
+            }
+        }
+    }
+}
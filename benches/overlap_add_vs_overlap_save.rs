@@ -0,0 +1,50 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use convolution::fft_convolver::FFTConvolver;
+use convolution::overlap_save_convolver::OverlapSaveConvolver;
+use convolution::{Convolution, Sample};
+
+const SAMPLE_RATE: usize = 48000;
+const SECONDS: usize = 10;
+
+fn run<T: Convolution>(response: &[Sample], block_size: usize, buffer_size: usize) -> Duration {
+    let mut convolver = T::init(response, block_size, response.len());
+    let input: Vec<Sample> = (0..buffer_size)
+        .map(|i| ((i * 7919) % 201) as Sample / 100.0 - 1.0)
+        .collect();
+    let mut output = vec![0.0; buffer_size];
+
+    let start = Instant::now();
+    for _ in 0..(SECONDS * SAMPLE_RATE / buffer_size) {
+        convolver.process(black_box(&input), &mut output);
+        black_box(&output);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>10} {:>8} {:>14} {:>14}",
+        "response", "block", "buffer", "overlap-add", "overlap-save"
+    );
+    for response_len in [4096, 48000] {
+        let response: Vec<Sample> = (0..response_len)
+            .map(|i| (-(i as Sample) / 8000.0).exp() * ((i % 17) as Sample - 8.0) / 8.0)
+            .collect();
+        for block_size in [64, 256, 1024] {
+            for buffer_size in [block_size / 2, block_size] {
+                let overlap_add = run::<FFTConvolver>(&response, block_size, buffer_size);
+                let overlap_save = run::<OverlapSaveConvolver>(&response, block_size, buffer_size);
+                println!(
+                    "{:>10} {:>10} {:>8} {:>12.1}ms {:>12.1}ms",
+                    response_len,
+                    block_size,
+                    buffer_size,
+                    overlap_add.as_secs_f64() * 1000.0,
+                    overlap_save.as_secs_f64() * 1000.0
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::config::{ConfigError, ConvolverConfig, FftBackend};
+use crate::direct_convolver::DirectConvolver;
+use crate::fft_convolver::LatencyMode;
+use crate::fft_convolver::{FFTConvolver, TwoStageFFTConvolver, HEAD_BLOCK_SIZE, TAIL_BLOCK_SIZE};
+use crate::{Convolution, DynConvolution, Sample};
+
+/// Engine an `AutoConvolver` runs a response with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// Time-domain convolution
+    Direct,
+    /// Uniformly partitioned `FFTConvolver`
+    Uniform,
+    /// `TwoStageFFTConvolver`
+    TwoStage,
+}
+
+/// Picks the cheapest engine for every response from its length and the host block size.
+///
+/// All engines are allocated for the max response length up front, so `update` stays real-time
+/// safe. Switching to another engine starts it from silence, like an update of an
+/// `FFTConvolver` drops the tail of the previous response.
+#[derive(Clone)]
+pub struct AutoConvolver {
+    engine: Engine,
+    block_size: usize,
+    // one convolver per engine, in the order of `Engine`
+    engines: [Box<dyn DynConvolution>; 3],
+    // the response of the active engine, to set it up again on `reconfigure`
+    response: Vec<Sample>,
+    response_len: usize,
+    fft_backend: FftBackend,
+}
+
+impl AutoConvolver {
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Returns the engine with the lowest estimated cost per sample.
+    pub fn select_engine(response_len: usize, block_size: usize) -> Engine {
+        let costs = [
+            (Engine::Direct, direct_cost(response_len)),
+            (Engine::Uniform, uniform_cost(response_len, block_size)),
+            (Engine::TwoStage, two_stage_cost(response_len, block_size)),
+        ];
+        costs
+            .into_iter()
+            .min_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+            .map(|(engine, _)| engine)
+            .unwrap()
+    }
+
+    pub fn fft_backend(&self) -> FftBackend {
+        self.fft_backend
+    }
+
+    fn active(&mut self) -> &mut dyn DynConvolution {
+        self.engines[self.engine as usize].as_mut()
+    }
+
+    fn build(
+        response: &[Sample],
+        max_block_size: usize,
+        max_response_length: usize,
+        fft_backend: FftBackend,
+    ) -> Self {
+        let engine = Self::select_engine(response.len(), max_block_size);
+        // only the selected engine gets the response
+        let response_for = |candidate: Engine| {
+            if candidate == engine {
+                response
+            } else {
+                &[]
+            }
+        };
+        let mut stored_response = response.to_vec();
+        stored_response.resize(max_response_length, 0.);
+        let mut uniform = FFTConvolver::init(
+            response_for(Engine::Uniform),
+            max_block_size,
+            max_response_length,
+        );
+        uniform.set_fft_backend(fft_backend);
+        let mut two_stage = TwoStageFFTConvolver::init(
+            response_for(Engine::TwoStage),
+            max_block_size,
+            max_response_length,
+        );
+        two_stage.set_fft_backend(fft_backend);
+        Self {
+            engine,
+            block_size: max_block_size,
+            engines: [
+                Box::new(DirectConvolver::init(
+                    response_for(Engine::Direct),
+                    max_block_size,
+                    max_response_length,
+                )),
+                Box::new(uniform),
+                Box::new(two_stage),
+            ],
+            response: stored_response,
+            response_len: response.len(),
+            fft_backend,
+        }
+    }
+}
+
+impl Convolution for AutoConvolver {
+    fn init(response: &[Sample], max_block_size: usize, max_response_length: usize) -> Self {
+        Self::build(
+            response,
+            max_block_size,
+            max_response_length,
+            FftBackend::default(),
+        )
+    }
+
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        if config.latency_mode() != LatencyMode::Zero {
+            return Err(ConfigError::UnsupportedLatencyMode(config.latency_mode()));
+        }
+        Ok(Self::build(
+            response,
+            config.block_size(),
+            config.max_response_length(),
+            config.fft_backend(),
+        ))
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        if response.len() > self.response.len() {
+            panic!("New impulse response is longer than max response length");
+        }
+        let engine = Self::select_engine(response.len(), self.block_size);
+        if engine != self.engine {
+            // release the previous engine, its history is stale once it is selected again
+            self.active().dyn_update(&[]);
+            self.active().dyn_reset();
+            self.engine = engine;
+        }
+        self.active().dyn_update(response);
+        self.response[..response.len()].copy_from_slice(response);
+        self.response_len = response.len();
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        self.active().dyn_process(input, output);
+    }
+
+    fn response_len(&self) -> usize {
+        self.response_len
+    }
+
+    fn reset(&mut self) {
+        self.active().dyn_reset();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        self.active().dyn_flush(output)
+    }
+
+    // selects the engine for the new block size
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        *self = Self::build(
+            &self.response[..self.response_len],
+            max_block_size,
+            max_response_length,
+            self.fft_backend,
+        );
+    }
+}
+
+// Rough number of arithmetic operations per output sample. A complex multiply-accumulate
+// counts as four real ones, a real FFT of size n as 1.5 * n * log2(n).
+fn direct_cost(response_len: usize) -> f64 {
+    response_len as f64
+}
+
+fn fft_cost(size: usize) -> f64 {
+    let size = size as f64;
+    1.5 * size * size.log2()
+}
+
+fn partitioned_cost(response_len: usize, block_size: usize, host_block_size: usize) -> f64 {
+    if response_len == 0 {
+        return 0.;
+    }
+    let block_size = block_size.next_power_of_two();
+    let partitions = ((response_len + block_size - 1) / block_size) as f64;
+    let spectrum_size = (block_size + 1) as f64;
+    // smaller host buffers repeat the transforms and the first partition for every call
+    let host_block_size = host_block_size.max(1);
+    let calls_per_block = ((block_size + host_block_size - 1) / host_block_size) as f64;
+    let per_call = 2. * fft_cost(2 * block_size) + 4. * spectrum_size;
+    let per_block = calls_per_block * per_call + 4. * spectrum_size * (partitions - 1.);
+    per_block / block_size as f64
+}
+
+fn uniform_cost(response_len: usize, block_size: usize) -> f64 {
+    partitioned_cost(response_len, block_size, block_size)
+}
+
+// with the head blocks as large as the host blocks, like `TwoStageFFTConvolver::init`
+fn two_stage_cost(response_len: usize, block_size: usize) -> f64 {
+    let head_block_size = block_size.next_power_of_two();
+    let tail_block_size = head_block_size * (TAIL_BLOCK_SIZE / HEAD_BLOCK_SIZE);
+    let head_len = response_len.min(tail_block_size);
+    let tail_len0 = response_len
+        .saturating_sub(tail_block_size)
+        .min(tail_block_size);
+    let tail_len = response_len.saturating_sub(2 * tail_block_size);
+    partitioned_cost(head_len, head_block_size, block_size)
+        + partitioned_cost(tail_len0, head_block_size, head_block_size)
+        + partitioned_cost(tail_len, tail_block_size, tail_block_size)
+}
+
+#[test]
+fn test_engine_selection() {
+    assert_eq!(AutoConvolver::select_engine(32, 64), Engine::Direct);
+    assert_eq!(AutoConvolver::select_engine(1024, 64), Engine::Uniform);
+    assert_eq!(AutoConvolver::select_engine(48000, 128), Engine::TwoStage);
+    assert_eq!(AutoConvolver::select_engine(48000, 4096), Engine::Uniform);
+}
@@ -0,0 +1,555 @@
+use rustfft::num_complex::Complex;
+
+use crate::fft_convolver::{complex_size, copy_and_pad, is_silent, sum, Fft};
+use crate::multi_channel_convolver::MultiChannelConvolver;
+use crate::{PlanarConvolution, Sample, TailFlush, SILENCE};
+
+// channels per batch, one vector lane each
+const LANES: usize = 8;
+
+// partitions accumulated per pass over the bins
+const PARTITION_GROUP: usize = 8;
+
+// Spectra of a batch of channels, each bin holds one value per lane
+#[derive(Clone)]
+struct BatchSpectrum {
+    re: Vec<[f32; LANES]>,
+    im: Vec<[f32; LANES]>,
+}
+
+impl BatchSpectrum {
+    fn new(size: usize) -> Self {
+        Self {
+            re: vec![[0.; LANES]; size],
+            im: vec![[0.; LANES]; size],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.re.fill([0.; LANES]);
+        self.im.fill([0.; LANES]);
+    }
+
+    fn scatter(&mut self, lane: usize, spectrum: &[Complex<f32>]) {
+        for ((re, im), value) in self.re.iter_mut().zip(&mut self.im).zip(spectrum) {
+            re[lane] = value.re;
+            im[lane] = value.im;
+        }
+    }
+
+    fn gather(&self, lane: usize, spectrum: &mut [Complex<f32>]) {
+        for ((re, im), value) in self.re.iter().zip(&self.im).zip(spectrum) {
+            *value = Complex::new(re[lane], im[lane]);
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re
+            .iter()
+            .chain(&self.im)
+            .all(|bin| bin.iter().all(|value| *value == 0.))
+    }
+}
+
+// Multiplies all lanes at once, the fixed lane count lets the compiler vectorize across channels
+fn batch_multiply_accumulate(result: &mut BatchSpectrum, a: &BatchSpectrum, b: &BatchSpectrum) {
+    let len = result.re.len();
+    let (result_re, result_im) = (&mut result.re[..len], &mut result.im[..len]);
+    let (a_re, a_im) = (&a.re[..len], &a.im[..len]);
+    let (b_re, b_im) = (&b.re[..len], &b.im[..len]);
+    for i in 0..len {
+        for lane in 0..LANES {
+            result_re[i][lane] += a_re[i][lane] * b_re[i][lane] - a_im[i][lane] * b_im[i][lane];
+            result_im[i][lane] += a_re[i][lane] * b_im[i][lane] + a_im[i][lane] * b_re[i][lane];
+        }
+    }
+}
+
+// Processing state of up to `LANES` channels
+#[derive(Clone)]
+struct Batch {
+    segments: Vec<BatchSpectrum>,
+    segments_ir: Vec<BatchSpectrum>,
+    // ascending indices of the partitions that are not all zero in any lane
+    active_partitions: Vec<usize>,
+    pre_multiplied: BatchSpectrum,
+}
+
+/// Uniformly partitioned convolution of many channels, which are convolved in batches of eight
+/// in the frequency domain.
+///
+/// Spectra are stored channel-interleaved, so the complex multiply-accumulate of a batch runs
+/// as one vector pass instead of one pass per channel. The transforms are still computed per
+/// channel. Otherwise it behaves like the `MultiChannelConvolver`: channels run in lockstep with
+/// one response each and updates drop the tail of the previous responses.
+///
+/// Partly filled batches still pay for all lanes, so with fewer than eight channels the
+/// channels are convolved one by one by an inner `MultiChannelConvolver`, which is faster there.
+/// See `benches/multi_channel.rs`.
+#[derive(Clone)]
+pub struct BatchedConvolver {
+    max_response_length: usize,
+    block_size: usize,
+    seg_count: usize,
+    channel_count: usize,
+    response_lens: Vec<usize>,
+    batches: Vec<Batch>,
+    overlaps: Vec<Vec<f32>>,
+    input_buffers: Vec<Vec<f32>>,
+    fft: Fft,
+    fft_buffer: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    conv: BatchSpectrum,
+    // (response, input) segment pairs of the partitions with a lag of at least one
+    partition_pairs: Vec<(usize, usize)>,
+    current: usize,
+    input_buffer_fill: usize,
+    tail_flush: TailFlush,
+    // processes all channels when there are too few to fill a batch
+    fallback: Option<MultiChannelConvolver>,
+}
+
+impl BatchedConvolver {
+    /// Creates a convolver that applies the same response to `channel_count` channels.
+    pub fn new(
+        response: &[Sample],
+        channel_count: usize,
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let mut convolver = Self::allocate(channel_count, block_size, max_response_length);
+        convolver.update(response);
+        convolver
+    }
+
+    /// Creates a convolver with one channel per response.
+    pub fn with_responses(
+        responses: &[&[Sample]],
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let mut convolver = Self::allocate(responses.len(), block_size, max_response_length);
+        convolver.update_channels(responses);
+        convolver
+    }
+
+    fn allocate(channel_count: usize, block_size: usize, max_response_length: usize) -> Self {
+        let block_size = block_size.next_power_of_two();
+        let seg_size = 2 * block_size;
+        let seg_count = (max_response_length as f64 / block_size as f64).ceil() as usize;
+        let fft_complex_size = complex_size(seg_size);
+
+        let fallback = (channel_count < LANES).then(|| {
+            let responses: Vec<&[Sample]> = vec![&[]; channel_count];
+            MultiChannelConvolver::with_responses(&responses, block_size, max_response_length)
+        });
+        // the batches are left empty when the fallback processes the channels
+        let batched_channels = if fallback.is_some() { 0 } else { channel_count };
+        let batch_count = (batched_channels + LANES - 1) / LANES;
+
+        let mut fft = Fft::default();
+        fft.init(seg_size);
+
+        let batch = Batch {
+            segments: vec![BatchSpectrum::new(fft_complex_size); seg_count],
+            segments_ir: vec![BatchSpectrum::new(fft_complex_size); seg_count],
+            active_partitions: Vec::with_capacity(seg_count),
+            pre_multiplied: BatchSpectrum::new(fft_complex_size),
+        };
+
+        Self {
+            max_response_length,
+            block_size,
+            seg_count,
+            channel_count,
+            response_lens: vec![0; channel_count],
+            batches: vec![batch; batch_count],
+            overlaps: vec![vec![0.; block_size]; batched_channels],
+            input_buffers: vec![vec![0.; block_size]; batched_channels],
+            fft,
+            fft_buffer: vec![0.; seg_size],
+            spectrum: vec![Complex::new(0., 0.); fft_complex_size],
+            conv: BatchSpectrum::new(fft_complex_size),
+            partition_pairs: Vec::with_capacity(seg_count),
+            current: 0,
+            input_buffer_fill: 0,
+            tail_flush: TailFlush::default(),
+            fallback,
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Applies `response` to all channels. Real-time safe.
+    pub fn update(&mut self, response: &[Sample]) {
+        if let Some(fallback) = &mut self.fallback {
+            fallback.update_channels(&[response; LANES][..self.channel_count]);
+            return;
+        }
+        self.check_response(response);
+        let block_size = self.block_size;
+        for i in 0..self.seg_count {
+            let partition = partition(response, i, block_size);
+            let empty = is_silent(partition, 0.);
+            if !empty {
+                copy_and_pad(&mut self.fft_buffer, partition, partition.len());
+                self.fft
+                    .forward(&mut self.fft_buffer, &mut self.spectrum)
+                    .unwrap();
+            }
+            for (batch_index, batch) in self.batches.iter_mut().enumerate() {
+                let lanes = std::cmp::min(LANES, self.channel_count - batch_index * LANES);
+                let segment = &mut batch.segments_ir[i];
+                segment.clear();
+                if !empty {
+                    for lane in 0..lanes {
+                        segment.scatter(lane, &self.spectrum);
+                    }
+                }
+            }
+        }
+        self.response_lens.fill(response.len());
+        self.finish_update();
+    }
+
+    /// Applies one response per channel at once. Real-time safe.
+    pub fn update_channels(&mut self, responses: &[&[Sample]]) {
+        assert_eq!(
+            responses.len(),
+            self.channel_count,
+            "one response per channel"
+        );
+        if let Some(fallback) = &mut self.fallback {
+            fallback.update_channels(responses);
+            return;
+        }
+        for response in responses {
+            self.check_response(response);
+        }
+
+        let block_size = self.block_size;
+        for batch in &mut self.batches {
+            for segment in &mut batch.segments_ir {
+                segment.clear();
+            }
+        }
+        for (channel, response) in responses.iter().enumerate() {
+            let batch = &mut self.batches[channel / LANES];
+            for i in 0..self.seg_count {
+                let partition = partition(response, i, block_size);
+                if is_silent(partition, 0.) {
+                    continue;
+                }
+                copy_and_pad(&mut self.fft_buffer, partition, partition.len());
+                self.fft
+                    .forward(&mut self.fft_buffer, &mut self.spectrum)
+                    .unwrap();
+                batch.segments_ir[i].scatter(channel % LANES, &self.spectrum);
+            }
+            self.response_lens[channel] = response.len();
+        }
+        self.finish_update();
+    }
+
+    /// Convolves planar buffers, one slice per channel of the same length.
+    pub fn process<I: AsRef<[Sample]>, O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[I],
+        output: &mut [O],
+    ) {
+        if let Some(fallback) = &mut self.fallback {
+            fallback.process(input, output);
+            return;
+        }
+        assert_eq!(input.len(), self.channel_count, "one input per channel");
+        assert_eq!(output.len(), self.channel_count, "one output per channel");
+        let len = input.first().map_or(0, |input| input.as_ref().len());
+        assert!(
+            input.iter().all(|input| input.as_ref().len() == len)
+                && output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        if self.seg_count == 0 {
+            for output in output.iter_mut() {
+                output.as_mut().fill(0.);
+            }
+            return;
+        }
+
+        let mut processed = 0;
+        while processed < len {
+            let processing =
+                std::cmp::min(len - processed, self.block_size - self.input_buffer_fill);
+            let chunk = processed..processed + processing;
+            for (batch_index, (input, output)) in input
+                .chunks(LANES)
+                .zip(output.chunks_mut(LANES))
+                .enumerate()
+            {
+                for (lane, input) in input.iter().enumerate() {
+                    self.transform_input(batch_index, lane, &input.as_ref()[chunk.clone()]);
+                }
+                self.multiply_batch(batch_index);
+                for (lane, output) in output.iter_mut().enumerate() {
+                    self.transform_output(batch_index, lane, &mut output.as_mut()[chunk.clone()]);
+                }
+            }
+            self.advance(processing);
+            processed += processing;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        0
+    }
+
+    pub fn response_len(&self, channel: usize) -> usize {
+        if let Some(fallback) = &self.fallback {
+            return fallback.response_len(channel);
+        }
+        self.response_lens[channel]
+    }
+
+    /// Number of samples the output of any channel may still be non-zero after the input went
+    /// silent.
+    pub fn tail_samples(&self) -> usize {
+        if let Some(fallback) = &self.fallback {
+            return fallback.tail_samples();
+        }
+        self.response_lens
+            .iter()
+            .map(|response_len| response_len.saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Clears all processing history. Real-time safe.
+    pub fn reset(&mut self) {
+        if let Some(fallback) = &mut self.fallback {
+            fallback.reset();
+            return;
+        }
+        for batch in &mut self.batches {
+            for segment in &mut batch.segments {
+                segment.clear();
+            }
+            batch.pre_multiplied.clear();
+        }
+        for (overlap, input_buffer) in self.overlaps.iter_mut().zip(&mut self.input_buffers) {
+            overlap.fill(0.);
+            input_buffer.fill(0.);
+        }
+        self.current = 0;
+        self.input_buffer_fill = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of every channel like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        if let Some(fallback) = &mut self.fallback {
+            return fallback.flush(output);
+        }
+        assert_eq!(output.len(), self.channel_count, "one output per channel");
+        let len = output.first_mut().map_or(0, |output| output.as_mut().len());
+        assert!(
+            output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let mut processed = 0;
+        while processed < written {
+            let processing = std::cmp::min(
+                std::cmp::min(written - processed, SILENCE.len()),
+                self.block_size - self.input_buffer_fill,
+            );
+            let chunk = processed..processed + processing;
+            for (batch_index, output) in output.chunks_mut(LANES).enumerate() {
+                for lane in 0..output.len() {
+                    self.transform_input(batch_index, lane, &SILENCE[..processing]);
+                }
+                self.multiply_batch(batch_index);
+                for (lane, output) in output.iter_mut().enumerate() {
+                    self.transform_output(batch_index, lane, &mut output.as_mut()[chunk.clone()]);
+                }
+            }
+            self.advance(processing);
+            processed += processing;
+        }
+        for output in output.iter_mut() {
+            output.as_mut()[written..].fill(0.);
+        }
+        self.tail_flush.advance(written);
+        written
+    }
+
+    fn check_response(&self, response: &[Sample]) {
+        if response.len() > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+    }
+
+    // the previous responses stop contributing, the input history is kept
+    fn finish_update(&mut self) {
+        for batch in &mut self.batches {
+            batch.active_partitions.clear();
+            for (i, segment) in batch.segments_ir.iter().enumerate() {
+                if !segment.is_zero() {
+                    batch.active_partitions.push(i);
+                }
+            }
+            batch.pre_multiplied.clear();
+        }
+        for overlap in &mut self.overlaps {
+            overlap.fill(0.);
+        }
+    }
+
+    // Transforms the input of one channel into its lane of the current segment
+    fn transform_input(&mut self, batch_index: usize, lane: usize, input: &[Sample]) {
+        let channel = batch_index * LANES + lane;
+        let input_buffer = &mut self.input_buffers[channel];
+        let input_buffer_pos = self.input_buffer_fill;
+        input_buffer[input_buffer_pos..input_buffer_pos + input.len()].copy_from_slice(input);
+
+        copy_and_pad(&mut self.fft_buffer, input_buffer, self.block_size);
+        self.fft
+            .forward(&mut self.fft_buffer, &mut self.spectrum)
+            .unwrap();
+        self.batches[batch_index].segments[self.current].scatter(lane, &self.spectrum);
+    }
+
+    // Multiplies the spectra of all lanes of a batch into `conv`
+    fn multiply_batch(&mut self, batch_index: usize) {
+        let batch = &mut self.batches[batch_index];
+        if self.input_buffer_fill == 0 {
+            self.partition_pairs.clear();
+            self.partition_pairs.extend(
+                batch
+                    .active_partitions
+                    .iter()
+                    .skip_while(|&&i| i == 0)
+                    .map(|&i| (i, (self.current + i) % self.seg_count)),
+            );
+
+            // bins outer within groups of partitions, so the sums of a bin stay in registers
+            // while the segments of a group are streamed
+            let pre_multiplied = &mut batch.pre_multiplied;
+            pre_multiplied.clear();
+            for pairs in self.partition_pairs.chunks(PARTITION_GROUP) {
+                for bin in 0..pre_multiplied.re.len() {
+                    let mut re = pre_multiplied.re[bin];
+                    let mut im = pre_multiplied.im[bin];
+                    for &(index_ir, index_audio) in pairs {
+                        let (a_re, a_im) = (
+                            &batch.segments_ir[index_ir].re[bin],
+                            &batch.segments_ir[index_ir].im[bin],
+                        );
+                        let (b_re, b_im) = (
+                            &batch.segments[index_audio].re[bin],
+                            &batch.segments[index_audio].im[bin],
+                        );
+                        for lane in 0..LANES {
+                            re[lane] += a_re[lane] * b_re[lane] - a_im[lane] * b_im[lane];
+                            im[lane] += a_re[lane] * b_im[lane] + a_im[lane] * b_re[lane];
+                        }
+                    }
+                    pre_multiplied.re[bin] = re;
+                    pre_multiplied.im[bin] = im;
+                }
+            }
+        }
+        self.conv.re.copy_from_slice(&batch.pre_multiplied.re);
+        self.conv.im.copy_from_slice(&batch.pre_multiplied.im);
+        if batch.active_partitions.first() == Some(&0) {
+            batch_multiply_accumulate(
+                &mut self.conv,
+                &batch.segments[self.current],
+                &batch.segments_ir[0],
+            );
+        }
+    }
+
+    // Transforms one lane of `conv` back and adds the overlap
+    fn transform_output(&mut self, batch_index: usize, lane: usize, output: &mut [Sample]) {
+        let channel = batch_index * LANES + lane;
+        let block_size = self.block_size;
+        let input_buffer_pos = self.input_buffer_fill;
+        let processing = output.len();
+
+        self.conv.gather(lane, &mut self.spectrum);
+        self.fft
+            .inverse(&mut self.spectrum, &mut self.fft_buffer)
+            .unwrap();
+
+        let overlap = &mut self.overlaps[channel];
+        sum(
+            output,
+            &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
+            &overlap[input_buffer_pos..input_buffer_pos + processing],
+        );
+
+        // Save the overlap once the block is complete
+        if input_buffer_pos + processing == block_size {
+            overlap.copy_from_slice(&self.fft_buffer[block_size..2 * block_size]);
+            self.input_buffers[channel].fill(0.);
+        }
+    }
+
+    // Moves on to the next segment once all channels completed the block
+    fn advance(&mut self, processed: usize) {
+        self.input_buffer_fill += processed;
+        if self.input_buffer_fill == self.block_size {
+            self.input_buffer_fill = 0;
+            self.current = if self.current > 0 {
+                self.current - 1
+            } else {
+                self.seg_count - 1
+            };
+        }
+    }
+}
+
+impl PlanarConvolution for BatchedConvolver {
+    fn input_count(&self) -> usize {
+        self.channel_count
+    }
+
+    fn output_count(&self) -> usize {
+        self.channel_count
+    }
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]) {
+        BatchedConvolver::process(self, input, output);
+    }
+
+    fn tail_samples(&self) -> usize {
+        BatchedConvolver::tail_samples(self)
+    }
+
+    fn reset(&mut self) {
+        BatchedConvolver::reset(self);
+    }
+
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize {
+        BatchedConvolver::flush(self, output)
+    }
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution> {
+        Box::new(self.clone())
+    }
+}
+
+// The samples of the response in partition `index`, empty beyond its end
+fn partition(response: &[Sample], index: usize, block_size: usize) -> &[Sample] {
+    let offset = std::cmp::min(index * block_size, response.len());
+    &response[offset..std::cmp::min(offset + block_size, response.len())]
+}
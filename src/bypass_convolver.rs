@@ -0,0 +1,170 @@
+use crate::config::{ConfigError, ConvolverConfig};
+use crate::crossfade_convolver::{Crossfader, FadingState, RaisedCosineMixer, Target};
+use crate::{flush_tail, Convolution, Sample, TailFlush};
+
+/// Wraps a convolver with a click-free bypass.
+///
+/// Toggling the bypass crossfades between the wet output of the convolver and the dry input.
+/// The convolver keeps processing while bypassed, so its tail is still warm when the bypass is
+/// released. The dry input is delayed by the latency of the convolver to stay aligned with the
+/// wet output.
+#[derive(Clone)]
+pub struct BypassConvolver<Convolver: Convolution> {
+    convolver: Convolver,
+    crossfader: Crossfader<RaisedCosineMixer>,
+    wet_buffer: Vec<Sample>,
+    dry_delay: Vec<Sample>,
+    dry_delay_pos: usize,
+    tail_flush: TailFlush,
+}
+
+// the wet output is mixed in as `Target::A`, the dry input as `Target::B`
+const WET: Target = Target::A;
+const DRY: Target = Target::B;
+
+impl<T: Convolution> BypassConvolver<T> {
+    pub fn new(convolver: T, max_buffer_size: usize, crossfade_samples: usize) -> Self {
+        assert!(
+            max_buffer_size > 0,
+            "max_buffer_size must be greater than 0"
+        );
+        Self {
+            crossfader: Crossfader::new(RaisedCosineMixer, crossfade_samples, 0),
+            wet_buffer: vec![0.0; max_buffer_size],
+            dry_delay: vec![0.0; convolver.latency()],
+            dry_delay_pos: 0,
+            tail_flush: TailFlush::default(),
+            convolver,
+        }
+    }
+
+    /// Starts fading towards the dry input (`true`) or the wet output (`false`).
+    ///
+    /// Toggling during a fade reverses it from the current mix position.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        self.crossfader.fade_into(if bypass { DRY } else { WET });
+    }
+
+    /// Whether the bypass is engaged, including while still fading towards the dry input.
+    pub fn is_bypassed(&self) -> bool {
+        self.crossfader.fading_state.target() == DRY
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        match self.crossfader.fading_state {
+            FadingState::Approaching(_) => true,
+            FadingState::Reached(_) => false,
+        }
+    }
+
+    pub fn convolver(&self) -> &T {
+        &self.convolver
+    }
+
+    /// A change of the latency of the convolver resizes the dry delay on the next `process` or
+    /// `reset`, which allocates and starts the delayed dry input from silence.
+    pub fn convolver_mut(&mut self) -> &mut T {
+        &mut self.convolver
+    }
+
+    // keeps the dry input aligned after the latency of the convolver changed
+    fn match_latency(&mut self) {
+        let latency = self.convolver.latency();
+        if self.dry_delay.len() != latency {
+            self.dry_delay = vec![0.0; latency];
+            self.dry_delay_pos = 0;
+        }
+    }
+
+    fn process_chunk(&mut self, input: &[Sample], output: &mut [Sample]) {
+        let wet_buffer = &mut self.wet_buffer[..input.len()];
+        self.convolver.process(input, wet_buffer);
+
+        for ((sample, wet), input) in output.iter_mut().zip(wet_buffer.iter()).zip(input) {
+            let dry = if self.dry_delay.is_empty() {
+                *input
+            } else {
+                let delayed = std::mem::replace(&mut self.dry_delay[self.dry_delay_pos], *input);
+                self.dry_delay_pos = (self.dry_delay_pos + 1) % self.dry_delay.len();
+                delayed
+            };
+            *sample = self.crossfader.mix(*wet, dry);
+        }
+    }
+}
+
+impl<Convolver: Convolution> Convolution for BypassConvolver<Convolver> {
+    fn init(response: &[Sample], max_block_size: usize, max_response_length: usize) -> Self {
+        let convolver = Convolver::init(response, max_block_size, max_response_length);
+        Self::new(convolver, max_block_size, max_block_size)
+    }
+
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        let convolver = Convolver::from_config(response, config)?;
+        Ok(Self::new(
+            convolver,
+            config.block_size(),
+            config.crossfade_samples(),
+        ))
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        self.convolver.update(response);
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "input and output must have the same length"
+        );
+        self.tail_flush.restart();
+        self.match_latency();
+
+        let max_buffer_size = self.wet_buffer.len();
+        for (input, output) in input
+            .chunks(max_buffer_size)
+            .zip(output.chunks_mut(max_buffer_size))
+        {
+            self.process_chunk(input, output);
+        }
+    }
+
+    fn latency(&self) -> usize {
+        self.convolver.latency()
+    }
+
+    fn response_len(&self) -> usize {
+        self.convolver.response_len()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.convolver.tail_samples()
+    }
+
+    fn reset(&mut self) {
+        self.convolver.reset();
+        self.crossfader.reset();
+        self.match_latency();
+        self.dry_delay.fill(0.0);
+        self.dry_delay_pos = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        assert!(max_block_size > 0, "max_buffer_size must be greater than 0");
+        self.convolver
+            .reconfigure(max_block_size, max_response_length);
+        self.wet_buffer = vec![0.0; max_block_size];
+        self.dry_delay = vec![0.0; self.convolver.latency()];
+        self.dry_delay_pos = 0;
+        self.crossfader.reset();
+        self.tail_flush = TailFlush::default();
+    }
+}
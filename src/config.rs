@@ -0,0 +1,365 @@
+use crate::fft_convolver::LatencyMode;
+use crate::{Convolution, Sample};
+
+/// FFT implementation used by the convolvers. Engines without transforms ignore it.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FftBackend {
+    /// Real-to-complex transforms of the realfft crate
+    #[default]
+    RealFft,
+    /// Complex transforms of the rustfft crate over the full length, slower than `RealFft`, e.g.
+    /// as a reference for it
+    RustFft,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigError {
+    ZeroBlockSize,
+    ZeroMaxResponseLength,
+    ZeroCrossfade,
+    InvalidSampleRate(f32),
+    /// A duration was given in seconds, but no sample rate is set
+    MissingSampleRate,
+    ResponseTooLong {
+        response_len: usize,
+        max_response_length: usize,
+    },
+    /// The engine cannot run with the requested latency mode
+    UnsupportedLatencyMode(LatencyMode),
+    /// The split point lies within the first block, whose size is the block size rounded up to
+    /// a power of two
+    SplitPointTooSmall {
+        split_point: usize,
+        block_size: usize,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroBlockSize => write!(f, "block size must be greater than 0"),
+            Self::ZeroMaxResponseLength => {
+                write!(f, "max response length must be greater than 0")
+            }
+            Self::ZeroCrossfade => write!(f, "crossfade length must be greater than 0"),
+            Self::InvalidSampleRate(sample_rate) => {
+                write!(f, "invalid sample rate {sample_rate}")
+            }
+            Self::MissingSampleRate => {
+                write!(f, "durations in seconds require a sample rate")
+            }
+            Self::ResponseTooLong {
+                response_len,
+                max_response_length,
+            } => write!(
+                f,
+                "response length {response_len} exceeds max response length {max_response_length}"
+            ),
+            Self::UnsupportedLatencyMode(latency_mode) => {
+                write!(f, "latency mode {latency_mode:?} is not supported")
+            }
+            Self::SplitPointTooSmall {
+                split_point,
+                block_size,
+            } => write!(
+                f,
+                "split point {split_point} is smaller than the block size {block_size}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Length {
+    Samples(usize),
+    Seconds(f32),
+}
+
+impl Length {
+    fn to_samples(self, sample_rate: Option<f32>) -> Result<usize, ConfigError> {
+        match self {
+            Self::Samples(samples) => Ok(samples),
+            Self::Seconds(seconds) => {
+                let sample_rate = sample_rate.ok_or(ConfigError::MissingSampleRate)?;
+                Ok((seconds * sample_rate).ceil() as usize)
+            }
+        }
+    }
+}
+
+/// Validated settings for constructing any convolver with `ConvolverConfig::build`.
+///
+/// ```
+/// use convolution::config::ConvolverConfig;
+/// use convolution::fft_convolver::FFTConvolver;
+///
+/// let config = ConvolverConfig::builder()
+///     .sample_rate(48000.0)
+///     .block_size(256)
+///     .max_response_seconds(2.0)
+///     .build()
+///     .unwrap();
+/// let convolver: FFTConvolver = config.build(&[1.0, 0.5, 0.25]).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvolverConfig {
+    block_size: usize,
+    max_response_length: usize,
+    latency_mode: LatencyMode,
+    crossfade_samples: usize,
+    split_point: Option<usize>,
+    sample_rate: Option<f32>,
+    fft_backend: FftBackend,
+}
+
+impl ConvolverConfig {
+    pub fn builder() -> ConvolverConfigBuilder {
+        ConvolverConfigBuilder::default()
+    }
+
+    /// Maximum number of samples passed to a single `process` call. Uniformly partitioned
+    /// engines use it as their partition size, the `TwoStageFFTConvolver` for its head.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn max_response_length(&self) -> usize {
+        self.max_response_length
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    /// Length of crossfades, defaults to the block size
+    pub fn crossfade_samples(&self) -> usize {
+        self.crossfade_samples
+    }
+
+    /// Start of the late part of the response, where the `TwoStageFFTConvolver` splits its
+    /// head from its tail. Other engines ignore it.
+    pub fn split_point(&self) -> Option<usize> {
+        self.split_point
+    }
+
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.sample_rate
+    }
+
+    pub fn fft_backend(&self) -> FftBackend {
+        self.fft_backend
+    }
+
+    // the same configuration for responses of a different length
+    pub(crate) fn with_max_response_length(&self, max_response_length: usize) -> Self {
+        Self {
+            max_response_length,
+            ..self.clone()
+        }
+    }
+
+    /// Creates a convolver of any type for the given response.
+    pub fn build<T: Convolution>(&self, response: &[Sample]) -> Result<T, ConfigError> {
+        if response.len() > self.max_response_length {
+            return Err(ConfigError::ResponseTooLong {
+                response_len: response.len(),
+                max_response_length: self.max_response_length,
+            });
+        }
+        T::from_config(response, self)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConvolverConfigBuilder {
+    block_size: Option<usize>,
+    max_response_length: Option<Length>,
+    latency_mode: LatencyMode,
+    crossfade: Option<Length>,
+    split_point: Option<Length>,
+    sample_rate: Option<f32>,
+    fft_backend: FftBackend,
+}
+
+impl ConvolverConfigBuilder {
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    pub fn max_response_length(mut self, samples: usize) -> Self {
+        self.max_response_length = Some(Length::Samples(samples));
+        self
+    }
+
+    /// Requires a sample rate
+    pub fn max_response_seconds(mut self, seconds: f32) -> Self {
+        self.max_response_length = Some(Length::Seconds(seconds));
+        self
+    }
+
+    pub fn latency_mode(mut self, latency_mode: LatencyMode) -> Self {
+        self.latency_mode = latency_mode;
+        self
+    }
+
+    pub fn crossfade_samples(mut self, samples: usize) -> Self {
+        self.crossfade = Some(Length::Samples(samples));
+        self
+    }
+
+    /// Requires a sample rate
+    pub fn crossfade_seconds(mut self, seconds: f32) -> Self {
+        self.crossfade = Some(Length::Seconds(seconds));
+        self
+    }
+
+    pub fn split_point(mut self, samples: usize) -> Self {
+        self.split_point = Some(Length::Samples(samples));
+        self
+    }
+
+    /// Requires a sample rate
+    pub fn split_seconds(mut self, seconds: f32) -> Self {
+        self.split_point = Some(Length::Seconds(seconds));
+        self
+    }
+
+    /// Requires a sample rate
+    pub fn split_milliseconds(mut self, milliseconds: f32) -> Self {
+        self.split_point = Some(Length::Seconds(milliseconds / 1000.0));
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: f32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn fft_backend(mut self, fft_backend: FftBackend) -> Self {
+        self.fft_backend = fft_backend;
+        self
+    }
+
+    pub fn build(self) -> Result<ConvolverConfig, ConfigError> {
+        if let Some(sample_rate) = self.sample_rate {
+            if !sample_rate.is_finite() || sample_rate <= 0.0 {
+                return Err(ConfigError::InvalidSampleRate(sample_rate));
+            }
+        }
+
+        let block_size = self.block_size.unwrap_or(0);
+        if block_size == 0 {
+            return Err(ConfigError::ZeroBlockSize);
+        }
+
+        let max_response_length = match self.max_response_length {
+            Some(length) => length.to_samples(self.sample_rate)?,
+            None => 0,
+        };
+        if max_response_length == 0 {
+            return Err(ConfigError::ZeroMaxResponseLength);
+        }
+
+        let crossfade_samples = match self.crossfade {
+            Some(length) => length.to_samples(self.sample_rate)?,
+            None => block_size,
+        };
+        if crossfade_samples == 0 {
+            return Err(ConfigError::ZeroCrossfade);
+        }
+
+        let split_point = match self.split_point {
+            Some(length) => Some(length.to_samples(self.sample_rate)?),
+            None => None,
+        };
+        if let Some(split_point) = split_point {
+            let first_block = block_size.next_power_of_two();
+            if split_point < first_block {
+                return Err(ConfigError::SplitPointTooSmall {
+                    split_point,
+                    block_size: first_block,
+                });
+            }
+        }
+
+        Ok(ConvolverConfig {
+            block_size,
+            max_response_length,
+            latency_mode: self.latency_mode,
+            crossfade_samples,
+            split_point,
+            sample_rate: self.sample_rate,
+            fft_backend: self.fft_backend,
+        })
+    }
+}
+
+#[test]
+fn test_config_validation() {
+    assert_eq!(
+        ConvolverConfig::builder().max_response_length(1024).build(),
+        Err(ConfigError::ZeroBlockSize)
+    );
+    assert_eq!(
+        ConvolverConfig::builder().block_size(128).build(),
+        Err(ConfigError::ZeroMaxResponseLength)
+    );
+    assert_eq!(
+        ConvolverConfig::builder()
+            .block_size(128)
+            .max_response_seconds(1.0)
+            .build(),
+        Err(ConfigError::MissingSampleRate)
+    );
+    assert_eq!(
+        ConvolverConfig::builder()
+            .sample_rate(0.0)
+            .block_size(128)
+            .max_response_length(1024)
+            .build(),
+        Err(ConfigError::InvalidSampleRate(0.0))
+    );
+
+    let config = ConvolverConfig::builder()
+        .sample_rate(48000.0)
+        .block_size(128)
+        .max_response_seconds(0.5)
+        .crossfade_seconds(0.01)
+        .build()
+        .unwrap();
+    assert_eq!(config.max_response_length(), 24000);
+    assert_eq!(config.crossfade_samples(), 480);
+    assert_eq!(
+        config
+            .build::<crate::fft_convolver::FFTConvolver>(&[0.0; 24001])
+            .err(),
+        Some(ConfigError::ResponseTooLong {
+            response_len: 24001,
+            max_response_length: 24000
+        })
+    );
+
+    let split = ConvolverConfig::builder()
+        .sample_rate(48000.0)
+        .block_size(100)
+        .max_response_length(24000);
+    assert_eq!(
+        split.clone().split_point(100).build(),
+        Err(ConfigError::SplitPointTooSmall {
+            split_point: 100,
+            block_size: 128
+        })
+    );
+    assert_eq!(
+        split
+            .split_milliseconds(20.0)
+            .build()
+            .unwrap()
+            .split_point(),
+        Some(960)
+    );
+}
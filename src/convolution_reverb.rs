@@ -0,0 +1,385 @@
+use crate::config::{ConfigError, ConvolverConfig};
+use crate::{Convolution, Sample, TailFlush, SILENCE};
+
+// Linear ramp towards a target value
+#[derive(Clone, Copy)]
+struct SmoothedValue {
+    value: Sample,
+    target: Sample,
+    step: Sample,
+    remaining: usize,
+}
+
+impl SmoothedValue {
+    fn new(value: Sample) -> Self {
+        Self {
+            value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    fn set(&mut self, target: Sample, ramp_samples: usize) {
+        self.target = target;
+        if ramp_samples == 0 {
+            self.complete();
+            return;
+        }
+        self.step = (target - self.value) / ramp_samples as Sample;
+        self.remaining = ramp_samples;
+    }
+
+    fn complete(&mut self) {
+        self.value = self.target;
+        self.remaining = 0;
+    }
+
+    fn next(&mut self) -> Sample {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.value = if self.remaining == 0 {
+                self.target
+            } else {
+                self.value + self.step
+            };
+        }
+        self.value
+    }
+}
+
+// Coefficient of a one-pole low-pass, 1 passes everything
+fn one_pole_coefficient(cutoff: Sample, sample_rate: Sample) -> Sample {
+    if cutoff >= 0.5 * sample_rate {
+        1.0
+    } else {
+        1.0 - (-2.0 * std::f32::consts::PI * cutoff.max(0.0) / sample_rate).exp()
+    }
+}
+
+// Low and high cut of one wet channel, 6 dB per octave each
+#[derive(Clone, Copy, Default)]
+struct Damping {
+    low_cut_state: Sample,
+    high_cut_state: Sample,
+}
+
+impl Damping {
+    fn process(&mut self, sample: Sample, low_cut: Sample, high_cut: Sample) -> Sample {
+        // the low cut subtracts the low-passed signal
+        self.low_cut_state += low_cut * (sample - self.low_cut_state);
+        let sample = sample - self.low_cut_state;
+        self.high_cut_state += high_cut * (sample - self.high_cut_state);
+        self.high_cut_state
+    }
+}
+
+/// Stereo convolution reverb built on any convolver.
+///
+/// The wet path runs through a low and high cut for damping and a mid/side stage for the
+/// width, then it is mixed with the dry input, which is delayed by the latency of the
+/// convolvers. Gains, width and cutoffs are ramped over the crossfade length of the
+/// configuration.
+///
+/// The pre-delay is applied by prepending silence to the responses instead of a delay line.
+/// Engines that skip empty partitions, like the `FFTConvolver`, process it for free. Changing it
+/// updates the convolvers, so it switches like a response update of the convolver type. A
+/// `CrossfadeConvolver` fades into the new delay while the reverb keeps ringing, whereas a plain
+/// `FFTConvolver` in `UpdateMode::Reset` cuts off the tail of the earlier input.
+#[derive(Clone)]
+pub struct ConvolutionReverb<Convolver: Convolution> {
+    convolvers: [Convolver; 2],
+    sample_rate: Sample,
+    ramp_samples: usize,
+    max_response_length: usize,
+    max_pre_delay: usize,
+    pre_delay: usize,
+    responses: [Vec<Sample>; 2],
+    // scratch for the responses with the pre-delay prepended
+    delayed_response: Vec<Sample>,
+    dry_gain: SmoothedValue,
+    wet_gain: SmoothedValue,
+    width: SmoothedValue,
+    low_cut: SmoothedValue,
+    high_cut: SmoothedValue,
+    damping: [Damping; 2],
+    wet_buffers: [Vec<Sample>; 2],
+    dry_delays: [Vec<Sample>; 2],
+    dry_delay_pos: usize,
+    tail_flush: TailFlush,
+}
+
+impl<T: Convolution> ConvolutionReverb<T> {
+    /// Creates a reverb with one response per channel. The configuration requires a sample rate,
+    /// its max response length limits the responses without the pre-delay.
+    ///
+    /// The reverb starts fully wet without pre-delay, damping or change of width.
+    pub fn new(
+        responses: [&[Sample]; 2],
+        config: &ConvolverConfig,
+        max_pre_delay: usize,
+    ) -> Result<Self, ConfigError> {
+        let sample_rate = config.sample_rate().ok_or(ConfigError::MissingSampleRate)?;
+        let max_response_length = config.max_response_length();
+        for response in responses {
+            if response.len() > max_response_length {
+                return Err(ConfigError::ResponseTooLong {
+                    response_len: response.len(),
+                    max_response_length,
+                });
+            }
+        }
+
+        let convolver_config = config.with_max_response_length(max_response_length + max_pre_delay);
+        let convolvers = [
+            convolver_config.build::<T>(responses[0])?,
+            convolver_config.build::<T>(responses[1])?,
+        ];
+        let latency = std::cmp::max(convolvers[0].latency(), convolvers[1].latency());
+        Ok(Self {
+            convolvers,
+            sample_rate,
+            ramp_samples: config.crossfade_samples(),
+            max_response_length,
+            max_pre_delay,
+            pre_delay: 0,
+            responses: responses.map(|response| {
+                let mut stored = Vec::with_capacity(max_response_length);
+                stored.extend_from_slice(response);
+                stored
+            }),
+            delayed_response: Vec::with_capacity(max_response_length + max_pre_delay),
+            dry_gain: SmoothedValue::new(0.0),
+            wet_gain: SmoothedValue::new(1.0),
+            width: SmoothedValue::new(1.0),
+            low_cut: SmoothedValue::new(one_pole_coefficient(0.0, sample_rate)),
+            high_cut: SmoothedValue::new(one_pole_coefficient(0.5 * sample_rate, sample_rate)),
+            damping: [Damping::default(); 2],
+            wet_buffers: std::array::from_fn(|_| vec![0.0; config.block_size()]),
+            dry_delays: std::array::from_fn(|_| vec![0.0; latency]),
+            dry_delay_pos: 0,
+            tail_flush: TailFlush::default(),
+        })
+    }
+
+    pub fn convolvers(&self) -> &[T; 2] {
+        &self.convolvers
+    }
+
+    /// Replaces the responses of both channels. Real-time safe if the convolvers update in a
+    /// real-time safe way.
+    pub fn update(&mut self, responses: [&[Sample]; 2]) {
+        for (stored, response) in self.responses.iter_mut().zip(responses) {
+            assert!(
+                response.len() <= self.max_response_length,
+                "New impulse response is longer than max response length"
+            );
+            stored.clear();
+            stored.extend_from_slice(response);
+        }
+        self.apply_responses();
+    }
+
+    /// Linear gain of the dry input.
+    pub fn set_dry_gain(&mut self, gain: Sample) {
+        self.dry_gain.set(gain, self.ramp_samples);
+    }
+
+    /// Linear gain of the wet output.
+    pub fn set_wet_gain(&mut self, gain: Sample) {
+        self.wet_gain.set(gain, self.ramp_samples);
+    }
+
+    /// Stereo width of the wet output: 0 is mono, 1 leaves it unchanged and larger values
+    /// widen it.
+    pub fn set_width(&mut self, width: Sample) {
+        self.width.set(width, self.ramp_samples);
+    }
+
+    /// Cutoff of the low cut on the wet path in Hz, 0 disables it.
+    pub fn set_low_cut(&mut self, cutoff: Sample) {
+        let coefficient = one_pole_coefficient(cutoff, self.sample_rate);
+        self.low_cut.set(coefficient, self.ramp_samples);
+    }
+
+    /// Cutoff of the high cut on the wet path in Hz, half the sample rate or more disables it.
+    pub fn set_high_cut(&mut self, cutoff: Sample) {
+        let coefficient = one_pole_coefficient(cutoff, self.sample_rate);
+        self.high_cut.set(coefficient, self.ramp_samples);
+    }
+
+    /// Delay of the wet output in samples, up to the max pre-delay given to `new`. The change
+    /// is applied like a response update, see the type documentation.
+    pub fn set_pre_delay(&mut self, samples: usize) {
+        assert!(
+            samples <= self.max_pre_delay,
+            "pre-delay exceeds the max pre-delay"
+        );
+        if samples != self.pre_delay {
+            self.pre_delay = samples;
+            self.apply_responses();
+        }
+    }
+
+    pub fn pre_delay(&self) -> usize {
+        self.pre_delay
+    }
+
+    /// Processes a stereo pair of buffers, given as two input and two output slices of the same
+    /// length.
+    pub fn process<I: AsRef<[Sample]>, O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[I],
+        output: &mut [O],
+    ) {
+        assert_eq!(input.len(), 2, "two input slices");
+        assert_eq!(output.len(), 2, "two output slices");
+        let [left, right] = output else {
+            unreachable!()
+        };
+        let (input_left, input_right) = (input[0].as_ref(), input[1].as_ref());
+        let (output_left, output_right) = (left.as_mut(), right.as_mut());
+        let len = input_left.len();
+        assert!(
+            input_right.len() == len && output_left.len() == len && output_right.len() == len,
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        // buffers larger than the block size are processed in several chunks
+        let max_buffer_size = self.wet_buffers[0].len();
+        let mut processed = 0;
+        while processed < len {
+            let chunk = processed..std::cmp::min(processed + max_buffer_size, len);
+            self.process_chunk(
+                [&input_left[chunk.clone()], &input_right[chunk.clone()]],
+                [
+                    &mut output_left[chunk.clone()],
+                    &mut output_right[chunk.clone()],
+                ],
+            );
+            processed = chunk.end;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        self.dry_delays[0].len()
+    }
+
+    pub fn tail_samples(&self) -> usize {
+        std::cmp::max(
+            self.convolvers[0].tail_samples(),
+            self.convolvers[1].tail_samples(),
+        )
+    }
+
+    /// Clears all processing history and completes parameter ramps. Real-time safe if the
+    /// convolvers reset in a real-time safe way.
+    pub fn reset(&mut self) {
+        for convolver in &mut self.convolvers {
+            convolver.reset();
+        }
+        for value in [
+            &mut self.dry_gain,
+            &mut self.wet_gain,
+            &mut self.width,
+            &mut self.low_cut,
+            &mut self.high_cut,
+        ] {
+            value.complete();
+        }
+        self.damping = [Damping::default(); 2];
+        for delay in &mut self.dry_delays {
+            delay.fill(0.0);
+        }
+        self.dry_delay_pos = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of both outputs like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        assert_eq!(output.len(), 2, "two output slices");
+        let [left, right] = output else {
+            unreachable!()
+        };
+        let (output_left, output_right) = (left.as_mut(), right.as_mut());
+        let len = output_left.len();
+        assert_eq!(
+            output_right.len(),
+            len,
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let max_chunk = std::cmp::min(SILENCE.len(), self.wet_buffers[0].len());
+        let mut processed = 0;
+        while processed < written {
+            let chunk = processed..std::cmp::min(processed + max_chunk, written);
+            let silence = &SILENCE[..chunk.len()];
+            self.process_chunk(
+                [silence, silence],
+                [
+                    &mut output_left[chunk.clone()],
+                    &mut output_right[chunk.clone()],
+                ],
+            );
+            processed = chunk.end;
+        }
+        output_left[written..].fill(0.);
+        output_right[written..].fill(0.);
+        self.tail_flush.advance(written);
+        written
+    }
+
+    // Updates the convolvers with the stored responses behind the pre-delay
+    fn apply_responses(&mut self) {
+        for (convolver, response) in self.convolvers.iter_mut().zip(&self.responses) {
+            self.delayed_response.clear();
+            self.delayed_response.resize(self.pre_delay, 0.0);
+            self.delayed_response.extend_from_slice(response);
+            convolver.update(&self.delayed_response);
+        }
+    }
+
+    fn process_chunk(&mut self, input: [&[Sample]; 2], output: [&mut [Sample]; 2]) {
+        let len = input[0].len();
+        for ((convolver, wet), input) in self
+            .convolvers
+            .iter_mut()
+            .zip(&mut self.wet_buffers)
+            .zip(input)
+        {
+            convolver.process(input, &mut wet[..len]);
+        }
+
+        let [output_left, output_right] = output;
+        let [wet_left, wet_right] = &self.wet_buffers;
+        let [damping_left, damping_right] = &mut self.damping;
+        for i in 0..len {
+            let (low_cut, high_cut) = (self.low_cut.next(), self.high_cut.next());
+            let left = damping_left.process(wet_left[i], low_cut, high_cut);
+            let right = damping_right.process(wet_right[i], low_cut, high_cut);
+
+            // mid/side
+            let mid = 0.5 * (left + right);
+            let side = 0.5 * (left - right) * self.width.next();
+            let wet_gain = self.wet_gain.next();
+            let (wet_left, wet_right) = ((mid + side) * wet_gain, (mid - side) * wet_gain);
+
+            let (dry_left, dry_right) = if self.dry_delays[0].is_empty() {
+                (input[0][i], input[1][i])
+            } else {
+                let pos = self.dry_delay_pos;
+                self.dry_delay_pos = (pos + 1) % self.dry_delays[0].len();
+                (
+                    std::mem::replace(&mut self.dry_delays[0][pos], input[0][i]),
+                    std::mem::replace(&mut self.dry_delays[1][pos], input[1][i]),
+                )
+            };
+            let dry_gain = self.dry_gain.next();
+
+            output_left[i] = dry_left * dry_gain + wet_left;
+            output_right[i] = dry_right * dry_gain + wet_right;
+        }
+    }
+}
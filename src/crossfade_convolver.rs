@@ -1,4 +1,5 @@
-use crate::{Convolution, Sample};
+use crate::config::{ConfigError, ConvolverConfig};
+use crate::{flush_tail, Convolution, Sample, ScheduledUpdate, TailFlush};
 
 #[derive(Clone)]
 struct CrossfadeConvolverCore<T: Convolution> {
@@ -13,7 +14,10 @@ pub struct CrossfadeConvolver<Convolver: Convolution> {
     buffer_a: Vec<Sample>,
     buffer_b: Vec<Sample>,
     stored_response: Vec<Sample>,
+    stored_response_len: usize,
     response_pending: bool,
+    scheduled_update: ScheduledUpdate,
+    tail_flush: TailFlush,
 }
 
 impl<T: Convolution> CrossfadeConvolver<T> {
@@ -23,6 +27,10 @@ impl<T: Convolution> CrossfadeConvolver<T> {
         max_buffer_size: usize,
         crossfade_samples: usize,
     ) -> Self {
+        assert!(
+            max_buffer_size > 0,
+            "max_buffer_size must be greater than 0"
+        );
         let stored_response = vec![0.0; max_response_length];
         Self {
             core: CrossfadeConvolverCore {
@@ -37,7 +45,10 @@ impl<T: Convolution> CrossfadeConvolver<T> {
             buffer_a: vec![0.0; max_buffer_size],
             buffer_b: vec![0.0; max_buffer_size],
             stored_response,
+            stored_response_len: 0,
             response_pending: false,
+            scheduled_update: ScheduledUpdate::with_capacity(max_response_length),
+            tail_flush: TailFlush::default(),
         }
     }
 }
@@ -48,6 +59,16 @@ impl<Convolver: Convolution> Convolution for CrossfadeConvolver<Convolver> {
         Self::new(convolver, response.len(), max_block_size, response.len())
     }
 
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        let convolver = Convolver::from_config(response, config)?;
+        Ok(Self::new(
+            convolver,
+            config.max_response_length(),
+            config.block_size(),
+            config.crossfade_samples(),
+        ))
+    }
+
     fn update(&mut self, response: &[Sample]) {
         if !self.is_crossfading() {
             swap(&mut self.core, response);
@@ -60,22 +81,92 @@ impl<Convolver: Convolution> Convolution for CrossfadeConvolver<Convolver> {
 
         self.stored_response[..response_len].copy_from_slice(response);
         self.stored_response[response_len..].fill(0.0);
+        self.stored_response_len = response_len;
         self.response_pending = true;
     }
 
     fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
-        if !self.is_crossfading() && self.response_pending {
-            swap(&mut self.core, &mut self.stored_response);
-            self.response_pending = false;
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "input and output must have the same length"
+        );
+        self.tail_flush.restart();
+
+        let mut processed = 0;
+        if let Some(offset) = self.scheduled_update.due_within(input.len()) {
+            self.process_chunks(&input[..offset], &mut output[..offset]);
+            let response = self.scheduled_update.take();
+            self.update(&response);
+            self.scheduled_update.restore(response);
+            processed = offset;
         }
+        self.process_chunks(&input[processed..], &mut output[processed..]);
+    }
+
+    fn latency(&self) -> usize {
+        std::cmp::max(
+            self.core.convolver_a.latency(),
+            self.core.convolver_b.latency(),
+        )
+    }
 
-        self.core.convolver_a.process(input, &mut self.buffer_a);
-        self.core.convolver_b.process(input, &mut self.buffer_b);
+    fn response_len(&self) -> usize {
+        match self.core.crossfader.fading_state.target() {
+            Target::A => self.core.convolver_a.response_len(),
+            Target::B => self.core.convolver_b.response_len(),
+        }
+    }
 
-        for i in 0..output.len() {
-            output[i] = self.core.crossfader.mix(self.buffer_a[i], self.buffer_b[i]);
+    fn tail_samples(&self) -> usize {
+        match self.core.crossfader.fading_state {
+            FadingState::Approaching(_) => std::cmp::max(
+                self.core.convolver_a.tail_samples(),
+                self.core.convolver_b.tail_samples(),
+            ),
+            FadingState::Reached(Target::A) => self.core.convolver_a.tail_samples(),
+            FadingState::Reached(Target::B) => self.core.convolver_b.tail_samples(),
         }
     }
+
+    fn reset(&mut self) {
+        self.core.convolver_a.reset();
+        self.core.convolver_b.reset();
+        self.core.crossfader.reset();
+        self.response_pending = false;
+        self.scheduled_update.cancel();
+        self.tail_flush = TailFlush::default();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    // a crossfade in progress is completed, a scheduled update is kept, and so is a pending
+    // response unless it is longer than the new max response length
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        assert!(max_block_size > 0, "max_buffer_size must be greater than 0");
+        self.core
+            .convolver_a
+            .reconfigure(max_block_size, max_response_length);
+        self.core
+            .convolver_b
+            .reconfigure(max_block_size, max_response_length);
+        self.core
+            .crossfader
+            .set_hold_samples(max_block_size.min(max_response_length));
+        self.core.crossfader.reset();
+        self.buffer_a = vec![0.0; max_block_size];
+        self.buffer_b = vec![0.0; max_block_size];
+        if self.stored_response_len > max_response_length {
+            self.response_pending = false;
+        }
+        self.stored_response.resize(max_response_length, 0.0);
+        self.scheduled_update.reserve(max_response_length);
+        self.tail_flush = TailFlush::default();
+    }
 }
 
 impl<Convolver: Convolution> CrossfadeConvolver<Convolver> {
@@ -85,6 +176,170 @@ impl<Convolver: Convolution> CrossfadeConvolver<Convolver> {
             FadingState::Reached(_) => false,
         }
     }
+
+    /// Installs an already initialized convolver in the inactive slot and crossfades into it.
+    ///
+    /// This allows switching engines together with the response, e.g. from a short
+    /// `FFTConvolver` to a long `TwoStageFFTConvolver` when used with an `EitherConvolver`.
+    /// The convolver should be built outside of the audio thread. The replaced convolver is
+    /// handed back so it can be dropped there as well.
+    ///
+    /// Both convolvers process every buffer, so `update` fades into one that has seen the
+    /// recent input. The installed convolver has only seen the input it was given before: a
+    /// freshly initialized one fades in from silence, and its tail builds up over the length of
+    /// its response. To avoid this, prime it with the most recent input before handing it over.
+    ///
+    /// While a crossfade is in progress nothing is changed and `convolver` is returned as error.
+    pub fn replace(&mut self, convolver: Convolver) -> Result<Convolver, Convolver> {
+        if self.is_crossfading() {
+            return Err(convolver);
+        }
+
+        // a pending response predates this convolver, so it must not override it
+        self.response_pending = false;
+
+        let core = &mut self.core;
+        let replaced = match core.crossfader.fading_state.target() {
+            Target::A => {
+                let replaced = std::mem::replace(&mut core.convolver_b, convolver);
+                core.crossfader.fade_into(Target::B);
+                replaced
+            }
+            Target::B => {
+                let replaced = std::mem::replace(&mut core.convolver_a, convolver);
+                core.crossfader.fade_into(Target::A);
+                replaced
+            }
+        };
+        Ok(replaced)
+    }
+
+    /// Starts the crossfade into `response` once `sample_offset` more samples have been
+    /// processed, splitting the buffer that contains that sample. The fade thus starts on the
+    /// same sample for any buffer size. If another crossfade is still in progress at that point,
+    /// the response is kept pending like with `update`, and its fade starts on the sample after
+    /// the running one has finished.
+    ///
+    /// Only one update can be scheduled, scheduling another one replaces it, and `reset` cancels
+    /// it. Real-time safe.
+    pub fn schedule_update(&mut self, response: &[Sample], sample_offset: usize) {
+        assert!(response.len() <= self.stored_response.len());
+        self.scheduled_update.schedule(response, sample_offset);
+    }
+
+    pub fn is_update_scheduled(&self) -> bool {
+        self.scheduled_update.is_scheduled()
+    }
+
+    // buffers larger than max_buffer_size are processed in several chunks, and a pending
+    // response starts its fade on the sample after the running one has finished
+    fn process_chunks(&mut self, input: &[Sample], output: &mut [Sample]) {
+        let max_buffer_size = self.buffer_a.len();
+        let mut processed = 0;
+        while processed < input.len() {
+            let mut len = std::cmp::min(input.len() - processed, max_buffer_size);
+            if self.response_pending && self.is_crossfading() {
+                len = std::cmp::min(len, self.core.crossfader.remaining_samples());
+            }
+            let chunk = processed..processed + len;
+            self.process_chunk(&input[chunk.clone()], &mut output[chunk]);
+            processed += len;
+        }
+    }
+
+    fn process_chunk(&mut self, input: &[Sample], output: &mut [Sample]) {
+        if !self.is_crossfading() && self.response_pending {
+            swap(&mut self.core, &self.stored_response);
+            self.response_pending = false;
+        }
+
+        let len = input.len();
+        let buffer_a = &mut self.buffer_a[..len];
+        let buffer_b = &mut self.buffer_b[..len];
+        self.core.convolver_a.process(input, buffer_a);
+        self.core.convolver_b.process(input, buffer_b);
+
+        for ((sample, a), b) in output.iter_mut().zip(buffer_a.iter()).zip(buffer_b.iter()) {
+            *sample = self.core.crossfader.mix(*a, *b);
+        }
+    }
+}
+
+/// Holds one of two convolver types, so that a `CrossfadeConvolver` can fade between engines
+/// that suit different responses.
+///
+/// `init` always creates the `Left` variant; use `CrossfadeConvolver::replace` to install a
+/// `Right` one.
+#[derive(Clone)]
+pub enum EitherConvolver<L: Convolution, R: Convolution> {
+    Left(L),
+    Right(R),
+}
+
+impl<L: Convolution, R: Convolution> Convolution for EitherConvolver<L, R> {
+    fn init(response: &[Sample], max_block_size: usize, max_response_length: usize) -> Self {
+        Self::Left(L::init(response, max_block_size, max_response_length))
+    }
+
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        Ok(Self::Left(L::from_config(response, config)?))
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        match self {
+            Self::Left(convolver) => convolver.update(response),
+            Self::Right(convolver) => convolver.update(response),
+        }
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        match self {
+            Self::Left(convolver) => convolver.process(input, output),
+            Self::Right(convolver) => convolver.process(input, output),
+        }
+    }
+
+    fn latency(&self) -> usize {
+        match self {
+            Self::Left(convolver) => convolver.latency(),
+            Self::Right(convolver) => convolver.latency(),
+        }
+    }
+
+    fn response_len(&self) -> usize {
+        match self {
+            Self::Left(convolver) => convolver.response_len(),
+            Self::Right(convolver) => convolver.response_len(),
+        }
+    }
+
+    fn tail_samples(&self) -> usize {
+        match self {
+            Self::Left(convolver) => convolver.tail_samples(),
+            Self::Right(convolver) => convolver.tail_samples(),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::Left(convolver) => convolver.reset(),
+            Self::Right(convolver) => convolver.reset(),
+        }
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        match self {
+            Self::Left(convolver) => convolver.flush(output),
+            Self::Right(convolver) => convolver.flush(output),
+        }
+    }
+
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        match self {
+            Self::Left(convolver) => convolver.reconfigure(max_block_size, max_response_length),
+            Self::Right(convolver) => convolver.reconfigure(max_block_size, max_response_length),
+        }
+    }
 }
 
 fn swap<T: Convolution>(core: &mut CrossfadeConvolverCore<T>, response: &[Sample]) {
@@ -101,6 +356,7 @@ fn swap<T: Convolution>(core: &mut CrossfadeConvolverCore<T>, response: &[Sample
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_crossfade_convolver_passthrough() {
     let mut response = [0.0; 1024];
     response[0] = 1.0;
@@ -154,7 +410,7 @@ impl Mixer for CosineMixer {
 }
 
 #[derive(Clone)]
-struct RaisedCosineMixer;
+pub(crate) struct RaisedCosineMixer;
 impl Mixer for RaisedCosineMixer {
     fn mix(&self, a: Sample, b: Sample, value: Sample) -> Sample {
         let rad = PI_HALF * value;
@@ -165,19 +421,19 @@ impl Mixer for RaisedCosineMixer {
 }
 
 #[derive(Clone, Copy, PartialEq)]
-enum Target {
+pub(crate) enum Target {
     A,
     B,
 }
 
 #[derive(Clone, Copy, PartialEq)]
-enum FadingState {
+pub(crate) enum FadingState {
     Reached(Target),
     Approaching(Target),
 }
 
 impl FadingState {
-    fn target(&self) -> Target {
+    pub(crate) fn target(&self) -> Target {
         match self {
             Self::Reached(target) => *target,
             Self::Approaching(target) => *target,
@@ -193,23 +449,23 @@ pub struct Crossfader<T: Mixer> {
     counter: i64,
     mix_value_step: Sample,
     mix_value: Sample,
-    fading_state: FadingState,
+    pub(crate) fading_state: FadingState,
 }
 
 impl<T: Mixer> Crossfader<T> {
-    fn new(mixer: T, fading_samples: usize, hold_samples: usize) -> Self {
+    pub(crate) fn new(mixer: T, fading_samples: usize, hold_samples: usize) -> Self {
         Self {
             mixer,
             fading_samples: fading_samples as i64,
             hold_samples: hold_samples as i64,
             counter: 0,
-            mix_value_step: 1.0 / fading_samples as Sample,
+            mix_value_step: 0.0,
             mix_value: 0.0,
             fading_state: FadingState::Reached(Target::A),
         }
     }
 
-    fn fade_into(&mut self, target: Target) {
+    pub(crate) fn fade_into(&mut self, target: Target) {
         let current_target = self.fading_state.target();
         if current_target == target {
             return;
@@ -219,27 +475,60 @@ impl<T: Mixer> Crossfader<T> {
             FadingState::Reached(_) => {
                 self.counter = -self.hold_samples;
                 self.fading_state = FadingState::Approaching(target);
-                self.mix_value_step = -self.mix_value_step;
             }
             FadingState::Approaching(_) => {
                 // note: should never be the case in the context of the crossfade convolver,
                 // which will swap responses only after a target is reached
-                if self.counter >= 0 {
+                if self.counter > 0 {
+                    // reverse from the current mix position
                     self.counter = self.fading_samples - self.counter;
                     self.fading_state = FadingState::Approaching(target);
-                    self.mix_value_step = -self.mix_value_step;
                 } else {
+                    // still holding, so the mix has not left `target` yet
+                    self.counter = 0;
                     self.fading_state = FadingState::Reached(target);
                 }
             }
         }
+
+        let step = 1.0 / self.fading_samples as Sample;
+        self.mix_value_step = match target {
+            Target::A => -step,
+            Target::B => step,
+        };
+    }
+
+    // number of samples until a fade in progress reaches its target, including the hold
+    pub(crate) fn remaining_samples(&self) -> usize {
+        match self.fading_state {
+            // the target is reached by the mix of the last sample, so at least one remains
+            FadingState::Approaching(_) => {
+                std::cmp::max(self.fading_samples - self.counter, 1) as usize
+            }
+            FadingState::Reached(_) => 0,
+        }
+    }
+
+    pub(crate) fn set_hold_samples(&mut self, hold_samples: usize) {
+        self.hold_samples = hold_samples as i64;
     }
 
-    fn mix(&mut self, a: Sample, b: Sample) -> Sample {
+    // completes a fade that is in progress right away
+    pub(crate) fn reset(&mut self) {
+        let target = self.fading_state.target();
+        self.fading_state = FadingState::Reached(target);
+        self.counter = 0;
+        self.mix_value = match target {
+            Target::A => 0.0,
+            Target::B => 1.0,
+        };
+    }
+
+    pub(crate) fn mix(&mut self, a: Sample, b: Sample) -> Sample {
         match self.fading_state {
             FadingState::Reached(target) => match target {
-                Target::A => return a,
-                Target::B => return b,
+                Target::A => a,
+                Target::B => b,
             },
             FadingState::Approaching(target) => {
                 self.counter += 1;
@@ -254,7 +543,7 @@ impl<T: Mixer> Crossfader<T> {
 
                 self.mix_value += self.mix_value_step;
 
-                if self.counter == self.fading_samples {
+                if self.counter >= self.fading_samples {
                     self.fading_state = FadingState::Reached(target);
                     match target {
                         Target::A => {
@@ -310,3 +599,34 @@ fn test_crossfader() {
         }
     }
 }
+
+#[test]
+fn test_crossfader_reversed_during_hold() {
+    let hold_samples = 4;
+    let fading_samples = 4;
+    let sample_a = 1.0;
+    let sample_b = 10.0;
+    let mut crossfader = Crossfader::new(LinearMixer, fading_samples, hold_samples);
+
+    crossfader.fade_into(Target::B);
+    for _ in 0..2 {
+        assert_eq!(crossfader.mix(sample_a, sample_b), sample_a);
+    }
+    crossfader.fade_into(Target::A);
+    assert!(crossfader.fading_state == FadingState::Reached(Target::A));
+    assert_eq!(crossfader.mix(sample_a, sample_b), sample_a);
+
+    // the next fade runs towards its own target
+    crossfader.fade_into(Target::B);
+    let mut previous = sample_a;
+    for i in 0..hold_samples + fading_samples {
+        let mixed_value = crossfader.mix(sample_a, sample_b);
+        assert!(mixed_value >= previous && mixed_value <= sample_b);
+        if i < hold_samples {
+            assert_eq!(mixed_value, sample_a);
+        }
+        previous = mixed_value;
+    }
+    assert_eq!(previous, sample_b);
+    assert!(crossfader.fading_state == FadingState::Reached(Target::B));
+}
@@ -0,0 +1,152 @@
+use crate::{flush_tail, Convolution, Sample, TailFlush};
+
+const LANES: usize = 8;
+
+/// Convolution in the time domain.
+///
+/// For responses of a few dozen samples this is cheaper than the FFT convolvers, which always
+/// transform blocks of twice their block size. As it computes the convolution sum exactly, it
+/// also serves as reference for the other convolvers.
+#[derive(Default, Clone)]
+pub struct DirectConvolver {
+    // the active response in reversed order, so that it lines up with the history
+    coefficients: Vec<Sample>,
+    response_len: usize,
+    // every input sample is written twice, one max response length apart, so the most recent
+    // samples are always available as one contiguous slice
+    history: Vec<Sample>,
+    history_pos: usize,
+    tail_flush: TailFlush,
+}
+
+impl Convolution for DirectConvolver {
+    fn init(response: &[Sample], _max_block_size: usize, max_response_length: usize) -> Self {
+        if max_response_length < response.len() {
+            panic!(
+                "max_response_length must be at least the length of the initial impulse response"
+            );
+        }
+
+        let mut convolver = Self {
+            coefficients: vec![0.; max_response_length],
+            response_len: 0,
+            history: vec![0.; 2 * max_response_length],
+            history_pos: 0,
+            tail_flush: TailFlush::default(),
+        };
+        convolver.update(response);
+        convolver
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        let response_len = response.len();
+        if response_len > self.coefficients.len() {
+            panic!("New impulse response is longer than max response length");
+        }
+
+        for (coefficient, sample) in self.coefficients.iter_mut().zip(response.iter().rev()) {
+            *coefficient = *sample;
+        }
+        self.response_len = response_len;
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "input and output must have the same length"
+        );
+        self.tail_flush.restart();
+        let max_response_length = self.coefficients.len();
+        if max_response_length == 0 {
+            output.fill(0.);
+            return;
+        }
+
+        let coefficients = &self.coefficients[..self.response_len];
+        for (sample, input) in output.iter_mut().zip(input) {
+            self.history[self.history_pos] = *input;
+            self.history[self.history_pos + max_response_length] = *input;
+
+            let end = self.history_pos + max_response_length + 1;
+            *sample = dot_product(&self.history[end - self.response_len..end], coefficients);
+
+            self.history_pos += 1;
+            if self.history_pos == max_response_length {
+                self.history_pos = 0;
+            }
+        }
+    }
+
+    fn response_len(&self) -> usize {
+        self.response_len
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.);
+        self.history_pos = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        let response = self.response();
+        *self = Self::init(&response, max_block_size, max_response_length);
+    }
+}
+
+impl DirectConvolver {
+    // the response that is currently applied
+    pub(crate) fn response(&self) -> Vec<Sample> {
+        self.coefficients[..self.response_len]
+            .iter()
+            .rev()
+            .copied()
+            .collect()
+    }
+}
+
+// Independent accumulators per lane let the compiler vectorize the loop
+fn dot_product(a: &[Sample], b: &[Sample]) -> Sample {
+    assert_eq!(a.len(), b.len());
+    let mut acc = [0.; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let remainder: Sample = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(a, b)| a * b)
+        .sum();
+    for (a, b) in a_chunks.zip(b_chunks) {
+        for lane in 0..LANES {
+            acc[lane] += a[lane] * b[lane];
+        }
+    }
+    acc.iter().sum::<Sample>() + remainder
+}
+
+#[test]
+fn test_direct_convolver() {
+    let response: Vec<Sample> = (0..37).map(|i| 1.0 / (i + 1) as Sample).collect();
+    let input: Vec<Sample> = (0..500).map(|i| ((i * 7) % 13) as Sample - 6.0).collect();
+    let mut convolver = DirectConvolver::init(&response[..5], 64, 64);
+    convolver.update(&response);
+
+    let mut output = vec![0.0; input.len()];
+    for (input, output) in input.chunks(17).zip(output.chunks_mut(17)) {
+        convolver.process(input, output);
+    }
+
+    for (n, sample) in output.iter().enumerate() {
+        let expected: Sample = (0..response.len().min(n + 1))
+            .map(|k| input[n - k] * response[k])
+            .sum();
+        assert!((sample - expected).abs() < 1e-4);
+    }
+}
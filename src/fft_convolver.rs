@@ -1,22 +1,33 @@
 use realfft::{ComplexToReal, FftError, RealFftPlanner, RealToComplex};
 use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
 use std::sync::Arc;
 
-use crate::{Convolution, Sample};
+use crate::config::{ConfigError, ConvolverConfig, FftBackend};
+use crate::{flush_tail, Convolution, Sample, ScheduledUpdate, TailFlush};
+
+#[derive(Clone)]
+enum Transforms {
+    Real {
+        forward: Arc<dyn RealToComplex<f32>>,
+        inverse: Arc<dyn ComplexToReal<f32>>,
+    },
+    // transforms over the full length, the upper half of the spectrum is implied by symmetry
+    Complex {
+        forward: Arc<dyn rustfft::Fft<f32>>,
+        inverse: Arc<dyn rustfft::Fft<f32>>,
+    },
+}
 
 #[derive(Clone)]
 pub struct Fft {
-    fft_forward: Arc<dyn RealToComplex<f32>>,
-    fft_inverse: Arc<dyn ComplexToReal<f32>>,
+    backend: FftBackend,
+    transforms: Transforms,
 }
 
 impl Default for Fft {
     fn default() -> Self {
-        let mut planner = RealFftPlanner::<f32>::new();
-        Self {
-            fft_forward: planner.plan_fft_forward(0),
-            fft_inverse: planner.plan_fft_inverse(0),
-        }
+        Self::with_backend(FftBackend::default())
     }
 }
 
@@ -27,19 +38,73 @@ impl std::fmt::Debug for Fft {
 }
 
 impl Fft {
+    pub fn with_backend(backend: FftBackend) -> Self {
+        Self {
+            backend,
+            transforms: Self::plan(backend, 0),
+        }
+    }
+
+    pub fn backend(&self) -> FftBackend {
+        self.backend
+    }
+
     pub fn init(&mut self, length: usize) {
-        let mut planner = RealFftPlanner::<f32>::new();
-        self.fft_forward = planner.plan_fft_forward(length);
-        self.fft_inverse = planner.plan_fft_inverse(length);
+        self.transforms = Self::plan(self.backend, length);
+    }
+
+    fn plan(backend: FftBackend, length: usize) -> Transforms {
+        match backend {
+            FftBackend::RealFft => {
+                let mut planner = RealFftPlanner::<f32>::new();
+                Transforms::Real {
+                    forward: planner.plan_fft_forward(length),
+                    inverse: planner.plan_fft_inverse(length),
+                }
+            }
+            FftBackend::RustFft => {
+                let mut planner = FftPlanner::<f32>::new();
+                Transforms::Complex {
+                    forward: planner.plan_fft_forward(length),
+                    inverse: planner.plan_fft_inverse(length),
+                }
+            }
+        }
     }
 
     pub fn forward(&self, input: &mut [f32], output: &mut [Complex<f32>]) -> Result<(), FftError> {
-        self.fft_forward.process(input, output)?;
+        match &self.transforms {
+            Transforms::Real { forward, .. } => forward.process(input, output)?,
+            Transforms::Complex { forward, .. } => {
+                assert_eq!(input.len(), forward.len());
+                assert_eq!(output.len(), complex_size(input.len()));
+                let mut buffer: Vec<Complex<f32>> =
+                    input.iter().map(|&value| Complex::new(value, 0.)).collect();
+                forward.process(&mut buffer);
+                output.copy_from_slice(&buffer[..output.len()]);
+            }
+        }
         Ok(())
     }
 
     pub fn inverse(&self, input: &mut [Complex<f32>], output: &mut [f32]) -> Result<(), FftError> {
-        self.fft_inverse.process(input, output)?;
+        match &self.transforms {
+            Transforms::Real { inverse, .. } => inverse.process(input, output)?,
+            Transforms::Complex { inverse, .. } => {
+                assert_eq!(output.len(), inverse.len());
+                assert_eq!(input.len(), complex_size(output.len()));
+                let len = output.len();
+                let mut buffer = vec![Complex::new(0., 0.); len];
+                buffer[..input.len()].copy_from_slice(input);
+                for bin in input.len()..len {
+                    buffer[bin] = buffer[len - bin].conj();
+                }
+                inverse.process(&mut buffer);
+                for (value, bin) in output.iter_mut().zip(&buffer) {
+                    *value = bin.re;
+                }
+            }
+        }
 
         // FFT Normalization
         let len = output.len();
@@ -59,6 +124,7 @@ pub fn copy_and_pad(dst: &mut [f32], src: &[f32], src_size: usize) {
     dst[src_size..].iter_mut().for_each(|value| *value = 0.);
 }
 
+#[allow(clippy::identity_op)]
 pub fn complex_multiply_accumulate(
     result: &mut [Complex<f32>],
     a: &[Complex<f32>],
@@ -84,6 +150,52 @@ pub fn complex_multiply_accumulate(
     }
 }
 
+pub fn complex_multiply_subtract(
+    result: &mut [Complex<f32>],
+    a: &[Complex<f32>],
+    b: &[Complex<f32>],
+) {
+    assert_eq!(result.len(), a.len());
+    assert_eq!(result.len(), b.len());
+    for ((result, a), b) in result.iter_mut().zip(a).zip(b) {
+        result.re -= a.re * b.re - a.im * b.im;
+        result.im -= a.re * b.im + a.im * b.re;
+    }
+}
+
+// Transforms response partitions back into the time domain
+pub(crate) fn response_from_partitions(
+    fft: &Fft,
+    segments_ir: &[Vec<Complex<f32>>],
+    block_size: usize,
+    response_len: usize,
+) -> Vec<Sample> {
+    let mut response = Vec::with_capacity(response_len);
+    let mut spectrum = vec![Complex::new(0., 0.); complex_size(2 * block_size)];
+    let mut buffer = vec![0.; 2 * block_size];
+    for segment in segments_ir {
+        if response.len() == response_len {
+            break;
+        }
+        spectrum.copy_from_slice(segment);
+        fft.inverse(&mut spectrum, &mut buffer).unwrap();
+        let len = std::cmp::min(block_size, response_len - response.len());
+        response.extend_from_slice(&buffer[..len]);
+    }
+    response
+}
+
+// largest power of two that is not greater than `value`, which must not be 0
+fn previous_power_of_two(value: usize) -> usize {
+    1 << (usize::BITS - 1 - value.leading_zeros())
+}
+
+// Whether no sample exceeds the threshold in magnitude
+pub(crate) fn is_silent(samples: &[Sample], threshold: Sample) -> bool {
+    samples.iter().all(|sample| sample.abs() <= threshold)
+}
+
+#[allow(clippy::identity_op)]
 pub fn sum(result: &mut [f32], a: &[f32], b: &[f32]) {
     assert_eq!(result.len(), a.len());
     assert_eq!(result.len(), b.len());
@@ -99,9 +211,51 @@ pub fn sum(result: &mut [f32], a: &[f32], b: &[f32]) {
         result[i] = a[i] + b[i];
     }
 }
+
+/// Determines how `FFTConvolver::update` switches to a new response.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UpdateMode {
+    /// Switch right away, dropping the output of the previous response.
+    #[default]
+    Reset,
+    /// Duck the output to silence over the given number of samples, then switch and recover
+    /// over as many samples. This suppresses the click of a `Reset` update at the cost of a
+    /// short gap, without running a second convolver like the `CrossfadeConvolver` does.
+    Ramp(usize),
+    /// Only input arriving after the update is convolved with the new response, while the tail
+    /// that the previous response produced from earlier input keeps ringing out.
+    ///
+    /// An update arriving before that tail has decayed renders the remainder of the tail
+    /// through the response it replaces instead.
+    PreserveHistory,
+}
+
+/// Determines when `FFTConvolver::process` produces its output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LatencyMode {
+    /// The output is produced within the call that receives the input. Partially filled blocks
+    /// are transformed on every call.
+    #[default]
+    Zero,
+    /// The output is delayed by one block, so the transforms only run once per block
+    /// regardless of the host buffer size.
+    Block,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Ramp {
+    #[default]
+    Idle,
+    // gain position counting down to 0, the pending response is applied at 0
+    Ducking(usize),
+    // gain position counting up to the ramp length
+    Recovering(usize),
+}
+
 #[derive(Default, Clone)]
 pub struct FFTConvolver {
     max_response_length: usize,
+    response_len: usize,
     block_size: usize,
     _seg_size: usize,
     seg_count: usize,
@@ -109,6 +263,8 @@ pub struct FFTConvolver {
     _fft_complex_size: usize,
     segments: Vec<Vec<Complex<f32>>>,
     segments_ir: Vec<Vec<Complex<f32>>>,
+    // the response in the time domain, zero beyond its length
+    ir: Vec<Sample>,
     fft_buffer: Vec<f32>,
     fft: Fft,
     pre_multiplied: Vec<Complex<f32>>,
@@ -117,6 +273,34 @@ pub struct FFTConvolver {
     current: usize,
     input_buffer: Vec<f32>,
     input_buffer_fill: usize,
+    update_mode: UpdateMode,
+    ramp: Ramp,
+    pending_response: Vec<Sample>,
+    pending_response_len: usize,
+    previous_segments_ir: Vec<Vec<Complex<f32>>>,
+    // ascending indices of the partitions that are not all zero, empty ones are skipped
+    active_partitions: Vec<usize>,
+    previous_active_partitions: Vec<usize>,
+    previous_active_seg_count: usize,
+    previous_response_len: usize,
+    blocks_since_update: usize,
+    // spectrum of the input that arrived in the current block before the update
+    straddle: Vec<Complex<f32>>,
+    straddle_active: bool,
+    latency_mode: LatencyMode,
+    delay_input: Vec<Sample>,
+    delay_output: Vec<Sample>,
+    delay_fill: usize,
+    tail_flush: TailFlush,
+    scheduled_update: ScheduledUpdate,
+    silence_threshold: Sample,
+    // whether the input block of each segment was silent, silent segments are skipped
+    silent_segments: Vec<bool>,
+    // number of consecutive silent input blocks, saturating
+    silent_blocks: usize,
+    input_buffer_silent: bool,
+    // number of consecutive silent input samples, saturating
+    silent_samples: usize,
 }
 
 impl Convolution for FFTConvolver {
@@ -144,6 +328,7 @@ impl Convolution for FFTConvolver {
         // prepare segments
         let segments = vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count];
         let mut segments_ir = Vec::new();
+        let mut active_partitions = Vec::with_capacity(seg_count);
 
         // prepare ir
         for i in 0..seg_count {
@@ -157,6 +342,11 @@ impl Convolution for FFTConvolver {
             copy_and_pad(&mut fft_buffer, &padded_ir[i * block_size..], size_copy);
             fft.forward(&mut fft_buffer, &mut segment).unwrap();
             segments_ir.push(segment);
+            if i < active_seg_count
+                && !is_silent(&padded_ir[i * block_size..i * block_size + size_copy], 0.)
+            {
+                active_partitions.push(i);
+            }
         }
 
         // prepare convolution buffers
@@ -173,6 +363,7 @@ impl Convolution for FFTConvolver {
 
         Self {
             max_response_length,
+            response_len: ir_len,
             block_size,
             _seg_size: seg_size,
             seg_count,
@@ -180,6 +371,7 @@ impl Convolution for FFTConvolver {
             _fft_complex_size: fft_complex_size,
             segments,
             segments_ir,
+            ir: padded_ir,
             fft_buffer,
             fft,
             pre_multiplied,
@@ -188,9 +380,39 @@ impl Convolution for FFTConvolver {
             current,
             input_buffer,
             input_buffer_fill,
+            update_mode: UpdateMode::Reset,
+            ramp: Ramp::Idle,
+            pending_response: Vec::new(),
+            pending_response_len: 0,
+            previous_segments_ir: Vec::new(),
+            active_partitions,
+            previous_active_partitions: Vec::new(),
+            previous_active_seg_count: 0,
+            previous_response_len: 0,
+            blocks_since_update: 0,
+            straddle: Vec::new(),
+            straddle_active: false,
+            latency_mode: LatencyMode::Zero,
+            delay_input: Vec::new(),
+            delay_output: Vec::new(),
+            delay_fill: 0,
+            tail_flush: TailFlush::default(),
+            scheduled_update: ScheduledUpdate::with_capacity(max_response_length),
+            silence_threshold: 0.,
+            silent_segments: vec![true; seg_count],
+            silent_blocks: usize::MAX,
+            input_buffer_silent: true,
+            silent_samples: usize::MAX,
         }
     }
 
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        let mut convolver = Self::init(response, config.block_size(), config.max_response_length());
+        convolver.set_latency_mode(config.latency_mode());
+        convolver.set_fft_backend(config.fft_backend());
+        Ok(convolver)
+    }
+
     fn update(&mut self, response: &[Sample]) {
         let new_ir_len = response.len();
 
@@ -198,6 +420,370 @@ impl Convolution for FFTConvolver {
             panic!("New impulse response is longer than max response length");
         }
 
+        match self.update_mode {
+            UpdateMode::Ramp(ramp_samples) if ramp_samples > 0 => {
+                self.pending_response[..new_ir_len].copy_from_slice(response);
+                self.pending_response_len = new_ir_len;
+                self.ramp = match self.ramp {
+                    Ramp::Idle => Ramp::Ducking(ramp_samples),
+                    Ramp::Ducking(position) | Ramp::Recovering(position) => Ramp::Ducking(position),
+                };
+            }
+            UpdateMode::PreserveHistory => self.apply_update_preserving_history(response),
+            _ => self.apply_update(response),
+        }
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        self.tail_flush.restart();
+
+        let mut processed = 0;
+        if let Some(offset) = self.scheduled_update.due_within(input.len()) {
+            self.process_in_latency_mode(&input[..offset], &mut output[..offset]);
+            let response = self.scheduled_update.take();
+            self.update(&response);
+            self.scheduled_update.restore(response);
+            processed = offset;
+        }
+        self.process_in_latency_mode(&input[processed..], &mut output[processed..]);
+    }
+
+    fn latency(&self) -> usize {
+        match self.latency_mode {
+            LatencyMode::Zero => 0,
+            LatencyMode::Block => self.block_size,
+        }
+    }
+
+    fn response_len(&self) -> usize {
+        self.response_len
+    }
+
+    fn tail_samples(&self) -> usize {
+        let mut response_len = self.response_len;
+        if self.previous_active_seg_count > 0 {
+            response_len = std::cmp::max(response_len, self.previous_response_len);
+        }
+        if let Ramp::Ducking(_) = self.ramp {
+            response_len = std::cmp::max(response_len, self.pending_response_len);
+        }
+        self.latency() + response_len.saturating_sub(1)
+    }
+
+    fn reset(&mut self) {
+        if let Ramp::Ducking(_) = self.ramp {
+            let pending_response = std::mem::take(&mut self.pending_response);
+            self.prepare_response(&pending_response[..self.pending_response_len]);
+            self.pending_response = pending_response;
+        }
+        self.ramp = Ramp::Idle;
+        self.previous_active_seg_count = 0;
+
+        for segment in &mut self.segments {
+            segment.fill(Complex::new(0., 0.));
+        }
+        self.fft_buffer.fill(0.);
+        self.pre_multiplied.fill(Complex::new(0., 0.));
+        self.conv.fill(Complex::new(0., 0.));
+        self.overlap.fill(0.);
+        self.current = 0;
+        self.input_buffer.fill(0.);
+        self.input_buffer_fill = 0;
+        self.delay_input.fill(0.);
+        self.delay_output.fill(0.);
+        self.delay_fill = 0;
+        self.tail_flush = TailFlush::default();
+        self.scheduled_update.cancel();
+        self.silent_segments.fill(true);
+        self.silent_blocks = usize::MAX;
+        self.input_buffer_silent = true;
+        self.silent_samples = usize::MAX;
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    // a pending ramp is completed, a previous response that is still ringing out is cut, a
+    // scheduled update is kept
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        let response = match self.ramp {
+            Ramp::Ducking(_) => self.pending_response[..self.pending_response_len].to_vec(),
+            _ => self.response(),
+        };
+        let mut convolver = Self::init(&response, max_block_size, max_response_length);
+        convolver.set_update_mode(self.update_mode);
+        convolver.set_latency_mode(self.latency_mode);
+        convolver.set_silence_threshold(self.silence_threshold);
+        convolver.set_fft_backend(self.fft.backend());
+        convolver.scheduled_update = std::mem::take(&mut self.scheduled_update);
+        convolver.scheduled_update.reserve(max_response_length);
+        *self = convolver;
+    }
+}
+
+impl FFTConvolver {
+    /// Sets how subsequent calls to `update` switch to the new response.
+    ///
+    /// Not real-time safe: `UpdateMode::Ramp` allocates storage for the pending response and
+    /// `UpdateMode::PreserveHistory` for the spectra of the previous response. A ramp that is in
+    /// progress is completed right away, a previous response that is still ringing out is cut.
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        if let Ramp::Ducking(_) = self.ramp {
+            let pending_response = std::mem::take(&mut self.pending_response);
+            self.apply_update(&pending_response[..self.pending_response_len]);
+        }
+        self.ramp = Ramp::Idle;
+        self.previous_active_seg_count = 0;
+
+        let fft_complex_size = complex_size(2 * self.block_size);
+        self.pending_response = Vec::new();
+        self.previous_segments_ir = Vec::new();
+        self.previous_active_partitions = Vec::new();
+        self.straddle = Vec::new();
+        match update_mode {
+            UpdateMode::Ramp(_) => self.pending_response = vec![0.; self.max_response_length],
+            UpdateMode::PreserveHistory => {
+                self.previous_segments_ir =
+                    vec![vec![Complex::new(0., 0.); fft_complex_size]; self.seg_count];
+                self.previous_active_partitions = Vec::with_capacity(self.seg_count);
+                self.straddle = vec![Complex::new(0., 0.); fft_complex_size];
+            }
+            UpdateMode::Reset => (),
+        }
+        self.pending_response_len = 0;
+        self.update_mode = update_mode;
+    }
+
+    pub fn update_mode(&self) -> UpdateMode {
+        self.update_mode
+    }
+
+    /// Sets whether the output is delayed by one block to save processing.
+    ///
+    /// Not real-time safe: `LatencyMode::Block` allocates the delay buffers.
+    pub fn set_latency_mode(&mut self, latency_mode: LatencyMode) {
+        let delay_len = match latency_mode {
+            LatencyMode::Zero => 0,
+            LatencyMode::Block => self.block_size,
+        };
+        self.delay_input = vec![0.; delay_len];
+        self.delay_output = vec![0.; delay_len];
+        self.delay_fill = 0;
+        self.latency_mode = latency_mode;
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    /// Sets the FFT implementation, defaults to `FftBackend::RealFft`.
+    ///
+    /// Not real-time safe: plans the transforms. The spectra of the response are kept, both
+    /// backends compute the same transform.
+    pub fn set_fft_backend(&mut self, backend: FftBackend) {
+        if backend != self.fft.backend() {
+            self.fft = Fft::with_backend(backend);
+            self.fft.init(2 * self.block_size);
+        }
+    }
+
+    pub fn fft_backend(&self) -> FftBackend {
+        self.fft.backend()
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    // the response that is currently applied
+    pub(crate) fn response(&self) -> Vec<Sample> {
+        self.ir[..self.response_len].to_vec()
+    }
+
+    /// Replaces the response samples starting at `offset` and only re-transforms the partitions
+    /// they fall into. The response grows if the samples reach beyond its end.
+    ///
+    /// Real-time safe. The edit applies to the whole input history, the output of the current
+    /// block blends between the previous and the edited response. While a ramp is in progress,
+    /// the pending response is edited instead.
+    pub fn update_range(&mut self, offset: usize, samples: &[Sample]) {
+        let end = offset + samples.len();
+        if end > self.max_response_length {
+            panic!("Edited range exceeds max response length");
+        }
+        if samples.is_empty() {
+            return;
+        }
+
+        if let Ramp::Ducking(_) = self.ramp {
+            if offset > self.pending_response_len {
+                self.pending_response[self.pending_response_len..offset].fill(0.);
+            }
+            self.pending_response[offset..end].copy_from_slice(samples);
+            self.pending_response_len = std::cmp::max(self.pending_response_len, end);
+            return;
+        }
+
+        let block_size = self.block_size;
+        let response_len = std::cmp::max(self.response_len, end);
+        self.ir[offset..end].copy_from_slice(samples);
+        for partition in offset / block_size..=(end - 1) / block_size {
+            let start = partition * block_size;
+            let partition_len = std::cmp::min(block_size, response_len - start);
+            copy_and_pad(&mut self.fft_buffer, &self.ir[start..], partition_len);
+
+            let empty = is_silent(&self.fft_buffer[..partition_len], 0.);
+            match (empty, self.active_partitions.binary_search(&partition)) {
+                (true, Ok(index)) => {
+                    self.active_partitions.remove(index);
+                }
+                (false, Err(index)) => self.active_partitions.insert(index, partition),
+                _ => (),
+            }
+            if empty {
+                self.segments_ir[partition].fill(Complex::new(0., 0.));
+            } else {
+                self.fft
+                    .forward(&mut self.fft_buffer, &mut self.segments_ir[partition])
+                    .unwrap();
+            }
+        }
+
+        self.response_len = response_len;
+        self.active_seg_count = ((response_len as f64 / block_size as f64).ceil()) as usize;
+    }
+
+    /// Sets the magnitude up to which input samples count as silence, defaults to 0.
+    ///
+    /// Once the input has been silent for longer than the response, blocks are not transformed
+    /// anymore and the output is silent. Silent input blocks are left out of the convolution, so
+    /// a threshold above 0 trades accuracy for less processing.
+    pub fn set_silence_threshold(&mut self, threshold: Sample) {
+        self.silence_threshold = threshold;
+    }
+
+    pub fn silence_threshold(&self) -> Sample {
+        self.silence_threshold
+    }
+
+    /// Whether the tail has decayed, so the output stays silent and costs no processing for as
+    /// long as the input is silent.
+    pub fn is_idle(&self) -> bool {
+        // with `LatencyMode::Block` the input is counted once it is convolved, up to a block late
+        self.silent_samples > self.tail_samples()
+    }
+
+    /// Applies `response` like `update` once `sample_offset` more samples have been processed,
+    /// splitting the buffer that contains that sample. The switch thus lands on the same sample
+    /// for any buffer size.
+    ///
+    /// Only one update can be scheduled, scheduling another one replaces it, and `reset` cancels
+    /// it. Real-time safe.
+    pub fn schedule_update(&mut self, response: &[Sample], sample_offset: usize) {
+        if response.len() > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+        self.scheduled_update.schedule(response, sample_offset);
+    }
+
+    pub fn is_update_scheduled(&self) -> bool {
+        self.scheduled_update.is_scheduled()
+    }
+
+    fn process_in_latency_mode(&mut self, input: &[Sample], output: &mut [Sample]) {
+        match self.latency_mode {
+            LatencyMode::Zero => self.process_ramped(input, output),
+            LatencyMode::Block => self.process_delayed(input, output),
+        }
+    }
+
+    fn process_ramped(&mut self, input: &[Sample], output: &mut [Sample]) {
+        if self.ramp == Ramp::Idle {
+            self.convolve(input, output);
+            return;
+        }
+
+        let ramp_samples = match self.update_mode {
+            UpdateMode::Ramp(ramp_samples) => ramp_samples,
+            _ => unreachable!("ramps only run in UpdateMode::Ramp"),
+        };
+
+        let mut processed = 0;
+        while processed < output.len() {
+            let remaining = output.len() - processed;
+            match self.ramp {
+                Ramp::Idle => {
+                    self.convolve(&input[processed..], &mut output[processed..]);
+                    processed += remaining;
+                }
+                Ramp::Ducking(position) => {
+                    let processing = std::cmp::min(remaining, position);
+                    let chunk = processed..processed + processing;
+                    self.convolve(&input[chunk.clone()], &mut output[chunk.clone()]);
+                    for (i, sample) in output[chunk].iter_mut().enumerate() {
+                        *sample *= (position - i - 1) as Sample / ramp_samples as Sample;
+                    }
+                    self.ramp = Ramp::Ducking(position - processing);
+                    if position == processing {
+                        let pending_response = std::mem::take(&mut self.pending_response);
+                        self.apply_update(&pending_response[..self.pending_response_len]);
+                        self.pending_response = pending_response;
+                        self.ramp = Ramp::Recovering(0);
+                    }
+                    processed += processing;
+                }
+                Ramp::Recovering(position) => {
+                    let processing = std::cmp::min(remaining, ramp_samples - position);
+                    let chunk = processed..processed + processing;
+                    self.convolve(&input[chunk.clone()], &mut output[chunk.clone()]);
+                    for (i, sample) in output[chunk].iter_mut().enumerate() {
+                        *sample *= (position + i + 1) as Sample / ramp_samples as Sample;
+                    }
+                    self.ramp = if position + processing == ramp_samples {
+                        Ramp::Idle
+                    } else {
+                        Ramp::Recovering(position + processing)
+                    };
+                    processed += processing;
+                }
+            }
+        }
+    }
+
+    // Collects the input in full blocks, which are convolved at once and output a block later
+    fn process_delayed(&mut self, input: &[Sample], output: &mut [Sample]) {
+        if self.delay_input.is_empty() {
+            output.fill(0.);
+            return;
+        }
+
+        let mut processed = 0;
+        while processed < output.len() {
+            let processing =
+                std::cmp::min(output.len() - processed, self.block_size - self.delay_fill);
+            let delay_range = self.delay_fill..self.delay_fill + processing;
+            self.delay_input[delay_range.clone()]
+                .copy_from_slice(&input[processed..processed + processing]);
+            output[processed..processed + processing]
+                .copy_from_slice(&self.delay_output[delay_range]);
+
+            self.delay_fill += processing;
+            if self.delay_fill == self.block_size {
+                let delay_input = std::mem::take(&mut self.delay_input);
+                let mut delay_output = std::mem::take(&mut self.delay_output);
+                self.process_ramped(&delay_input, &mut delay_output);
+                self.delay_input = delay_input;
+                self.delay_output = delay_output;
+                self.delay_fill = 0;
+            }
+            processed += processing;
+        }
+    }
+
+    fn apply_update(&mut self, response: &[Sample]) {
         if self.max_response_length == 0 {
             return;
         }
@@ -207,9 +793,49 @@ impl Convolution for FFTConvolver {
         self.pre_multiplied.fill(Complex::new(0., 0.));
         self.overlap.fill(0.);
 
+        self.prepare_response(response);
+    }
+
+    fn apply_update_preserving_history(&mut self, response: &[Sample]) {
+        if self.max_response_length == 0 {
+            return;
+        }
+
+        // Keep the previous response for the input that arrived before the update.
+        // The overlap and the pre-multiplied partitions only contain earlier input and stay.
+        std::mem::swap(&mut self.segments_ir, &mut self.previous_segments_ir);
+        std::mem::swap(
+            &mut self.active_partitions,
+            &mut self.previous_active_partitions,
+        );
+        self.previous_active_seg_count = self.active_seg_count;
+        self.previous_response_len = self.response_len;
+        self.blocks_since_update = 0;
+
+        // the input buffer is zero beyond its fill level
+        self.straddle_active = self.input_buffer_fill > 0;
+        if self.straddle_active {
+            copy_and_pad(&mut self.fft_buffer, &self.input_buffer, self.block_size);
+            self.fft
+                .forward(&mut self.fft_buffer, &mut self.straddle)
+                .unwrap();
+        }
+
+        self.prepare_response(response);
+    }
+
+    fn prepare_response(&mut self, response: &[Sample]) {
+        let new_ir_len = response.len();
+        self.ir[..new_ir_len].copy_from_slice(response);
+        if self.response_len > new_ir_len {
+            self.ir[new_ir_len..self.response_len].fill(0.);
+        }
+        self.response_len = new_ir_len;
+
         self.active_seg_count = ((new_ir_len as f64 / self.block_size as f64).ceil()) as usize;
 
-        // Prepare IR
+        // Prepare IR, partitions that are all zero are not transformed
+        self.active_partitions.clear();
         for i in 0..self.active_seg_count {
             let segment = &mut self.segments_ir[i];
             let remaining = new_ir_len - (i * self.block_size);
@@ -218,12 +844,14 @@ impl Convolution for FFTConvolver {
             } else {
                 remaining
             };
-            copy_and_pad(
-                &mut self.fft_buffer,
-                &response[i * self.block_size..],
-                size_copy,
-            );
+            let partition = &response[i * self.block_size..i * self.block_size + size_copy];
+            if is_silent(partition, 0.) {
+                segment.fill(Complex::new(0., 0.));
+                continue;
+            }
+            copy_and_pad(&mut self.fft_buffer, partition, size_copy);
             self.fft.forward(&mut self.fft_buffer, segment).unwrap();
+            self.active_partitions.push(i);
         }
 
         // Clear remaining segments
@@ -232,8 +860,25 @@ impl Convolution for FFTConvolver {
         }
     }
 
-    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
-        if self.active_seg_count == 0 {
+    // Counts the silent samples at the end of the input so far, returns whether all of `input`
+    // is silent. A single pass serves both, as it stops at the last sample above the threshold.
+    fn track_silence(&mut self, input: &[Sample]) -> bool {
+        let threshold = self.silence_threshold;
+        match input.iter().rposition(|sample| sample.abs() > threshold) {
+            Some(position) => {
+                self.silent_samples = input.len() - 1 - position;
+                false
+            }
+            None => {
+                self.silent_samples = self.silent_samples.saturating_add(input.len());
+                true
+            }
+        }
+    }
+
+    fn convolve(&mut self, input: &[Sample], output: &mut [Sample]) {
+        if self.active_seg_count == 0 && self.previous_active_seg_count == 0 {
+            self.track_silence(input);
             output.fill(0.);
             return;
         }
@@ -247,51 +892,85 @@ impl Convolution for FFTConvolver {
             );
 
             let input_buffer_pos = self.input_buffer_fill;
+            let chunk = &input[processed..processed + processing];
             self.input_buffer[input_buffer_pos..input_buffer_pos + processing]
-                .clone_from_slice(&input[processed..processed + processing]);
+                .clone_from_slice(chunk);
+            self.input_buffer_silent &= self.track_silence(chunk);
 
-            // Forward FFT
-            copy_and_pad(&mut self.fft_buffer, &self.input_buffer, self.block_size);
-            if let Err(_err) = self
-                .fft
-                .forward(&mut self.fft_buffer, &mut self.segments[self.current])
-            {
-                output.fill(0.);
-                return; // error!
-            }
+            // Every block within reach of the response is silent
+            let seg_count = std::cmp::max(self.active_seg_count, self.previous_active_seg_count);
+            let idle = self.input_buffer_silent && self.silent_blocks >= seg_count;
 
-            // complex multiplication
-            if input_buffer_was_empty {
-                self.pre_multiplied.fill(Complex { re: 0., im: 0. });
-                for i in 1..self.active_seg_count {
-                    let index_ir = i;
-                    let index_audio = (self.current + i) % self.seg_count;
+            if idle {
+                // the history is silent in case the input resumes within this block
+                if input_buffer_was_empty {
+                    self.pre_multiplied.fill(Complex { re: 0., im: 0. });
+                }
+                output[processed..processed + processing].fill(0.);
+            } else {
+                // Forward FFT
+                copy_and_pad(&mut self.fft_buffer, &self.input_buffer, self.block_size);
+                if let Err(_err) = self
+                    .fft
+                    .forward(&mut self.fft_buffer, &mut self.segments[self.current])
+                {
+                    output.fill(0.);
+                    return; // error!
+                }
+
+                // complex multiplication
+                if input_buffer_was_empty {
+                    self.pre_multiplied.fill(Complex { re: 0., im: 0. });
+                    if self.previous_active_seg_count > 0 {
+                        self.pre_multiply_preserving_history();
+                    } else {
+                        for &i in self.active_partitions.iter().skip_while(|&&i| i == 0) {
+                            let index_ir = i;
+                            let index_audio = (self.current + i) % self.seg_count;
+                            if self.silent_segments[index_audio] {
+                                continue;
+                            }
+                            complex_multiply_accumulate(
+                                &mut self.pre_multiplied,
+                                &self.segments_ir[index_ir],
+                                &self.segments[index_audio],
+                            );
+                        }
+                    }
+                }
+                self.conv.clone_from_slice(&self.pre_multiplied);
+                if self.active_partitions.first() == Some(&0) {
                     complex_multiply_accumulate(
-                        &mut self.pre_multiplied,
-                        &self.segments_ir[index_ir],
-                        &self.segments[index_audio],
+                        &mut self.conv,
+                        &self.segments[self.current],
+                        &self.segments_ir[0],
                     );
                 }
-            }
-            self.conv.clone_from_slice(&self.pre_multiplied);
-            complex_multiply_accumulate(
-                &mut self.conv,
-                &self.segments[self.current],
-                &self.segments_ir[0],
-            );
+                if self.previous_active_seg_count > 0
+                    && self.blocks_since_update == 0
+                    && self.straddle_active
+                {
+                    complex_multiply_accumulate(
+                        &mut self.conv,
+                        &self.straddle,
+                        &self.previous_segments_ir[0],
+                    );
+                    complex_multiply_subtract(&mut self.conv, &self.straddle, &self.segments_ir[0]);
+                }
 
-            // Backward FFT
-            if let Err(_err) = self.fft.inverse(&mut self.conv, &mut self.fft_buffer) {
-                output.fill(0.);
-                return; // error!
-            }
+                // Backward FFT
+                if let Err(_err) = self.fft.inverse(&mut self.conv, &mut self.fft_buffer) {
+                    output.fill(0.);
+                    return; // error!
+                }
 
-            // Add overlap
-            sum(
-                &mut output[processed..processed + processing],
-                &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
-                &self.overlap[input_buffer_pos..input_buffer_pos + processing],
-            );
+                // Add overlap
+                sum(
+                    &mut output[processed..processed + processing],
+                    &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
+                    &self.overlap[input_buffer_pos..input_buffer_pos + processing],
+                );
+            }
 
             // Input buffer full => Next block
             self.input_buffer_fill += processing;
@@ -300,8 +979,21 @@ impl Convolution for FFTConvolver {
                 self.input_buffer.fill(0.);
                 self.input_buffer_fill = 0;
                 // Save the overlap
-                self.overlap
-                    .clone_from_slice(&self.fft_buffer[self.block_size..self.block_size * 2]);
+                if idle {
+                    self.overlap.fill(0.);
+                } else {
+                    self.overlap
+                        .clone_from_slice(&self.fft_buffer[self.block_size..self.block_size * 2]);
+                }
+
+                // Remember silent blocks, the segment is not transformed while idle
+                self.silent_segments[self.current] = self.input_buffer_silent;
+                self.silent_blocks = if self.input_buffer_silent {
+                    self.silent_blocks.saturating_add(1)
+                } else {
+                    0
+                };
+                self.input_buffer_silent = true;
 
                 // Update the current segment
                 self.current = if self.current > 0 {
@@ -309,13 +1001,71 @@ impl Convolution for FFTConvolver {
                 } else {
                     self.seg_count - 1
                 };
+
+                // The split is done once the last input block before the update has passed all
+                // partitions of both responses, until then a longer current response must not
+                // reach the input from before the update. The overlap of the last partition of
+                // the previous response is output one block later, it keeps the convolver from
+                // going idle until then.
+                if self.previous_active_seg_count > 0 {
+                    self.blocks_since_update += 1;
+                    let seg_count =
+                        std::cmp::max(self.active_seg_count, self.previous_active_seg_count + 1);
+                    if self.blocks_since_update >= seg_count {
+                        self.previous_active_seg_count = 0;
+                    }
+                }
             }
             processed += processing;
         }
     }
+
+    // Blocks that arrived before the update are multiplied with the previous response, later
+    // ones with the current response. The block during which the update happened contributes
+    // to both, split by the spectrum of the input that preceded the update.
+    fn pre_multiply_preserving_history(&mut self) {
+        let blocks_since_update = self.blocks_since_update;
+        let seg_count = std::cmp::max(self.active_seg_count, self.previous_active_seg_count);
+        for i in 1..seg_count {
+            let index_audio = (self.current + i) % self.seg_count;
+            if self.silent_segments[index_audio] {
+                continue;
+            }
+            let straddling = i == blocks_since_update && self.straddle_active;
+            if i <= blocks_since_update && self.active_partitions.binary_search(&i).is_ok() {
+                complex_multiply_accumulate(
+                    &mut self.pre_multiplied,
+                    &self.segments_ir[i],
+                    &self.segments[index_audio],
+                );
+                if straddling {
+                    complex_multiply_subtract(
+                        &mut self.pre_multiplied,
+                        &self.segments_ir[i],
+                        &self.straddle,
+                    );
+                }
+            }
+            if (i > blocks_since_update || straddling)
+                && self.previous_active_partitions.binary_search(&i).is_ok()
+            {
+                let segment = if straddling {
+                    &self.straddle
+                } else {
+                    &self.segments[index_audio]
+                };
+                complex_multiply_accumulate(
+                    &mut self.pre_multiplied,
+                    &self.previous_segments_ir[i],
+                    segment,
+                );
+            }
+        }
+    }
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_fft_convolver_passthrough() {
     let mut response = [0.0; 1024];
     response[0] = 1.0;
@@ -341,15 +1091,79 @@ pub struct TwoStageFFTConvolver {
     tail_input: Vec<Sample>,
     tail_input_fill: usize,
     precalculated_pos: usize,
+    response_len: usize,
+    head_block_size: usize,
+    tail_block_size: usize,
+    // length of the head, the first tail block starts here
+    split: usize,
+    // scratch for the response of the first tail block behind its leading silence
+    tail_response0: Vec<Sample>,
+    tail_flush: TailFlush,
 }
 
-const HEAD_BLOCK_SIZE: usize = 128;
-const TAIL_BLOCK_SIZE: usize = 1024;
+pub(crate) const HEAD_BLOCK_SIZE: usize = 128;
+pub(crate) const TAIL_BLOCK_SIZE: usize = 1024;
+
+impl TwoStageFFTConvolver {
+    /// Creates a convolver that processes the first `tail_block_size` samples of the response
+    /// in blocks of `head_block_size` and the rest in blocks of `tail_block_size`.
+    ///
+    /// Both block sizes are rounded up to the next power of two.
+    pub fn new(
+        impulse_response: &[Sample],
+        head_block_size: usize,
+        tail_block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let tail_block_size = tail_block_size.next_power_of_two();
+        Self::build(
+            impulse_response,
+            head_block_size,
+            tail_block_size,
+            tail_block_size,
+            max_response_length,
+        )
+    }
 
-impl Convolution for TwoStageFFTConvolver {
-    fn init(impulse_response: &[Sample], _block_size: usize, max_response_length: usize) -> Self {
-        let head_block_size = HEAD_BLOCK_SIZE;
-        let tail_block_size = TAIL_BLOCK_SIZE;
+    /// Creates a convolver whose head covers the first `split` samples of the response, see
+    /// `process_split`.
+    ///
+    /// The tail block size is the largest power of two up to `split`, so the head processes
+    /// less than twice as many samples as with `new`. `split` must be at least the head block
+    /// size rounded up to the next power of two.
+    pub fn with_split(
+        impulse_response: &[Sample],
+        head_block_size: usize,
+        split: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let head_block_size = head_block_size.next_power_of_two();
+        assert!(
+            split >= head_block_size,
+            "split must be at least head_block_size"
+        );
+        Self::build(
+            impulse_response,
+            head_block_size,
+            previous_power_of_two(split),
+            split,
+            max_response_length,
+        )
+    }
+
+    // `tail_block_size` must be a power of two with `tail_block_size <= split < 2 * tail_block_size`
+    fn build(
+        impulse_response: &[Sample],
+        head_block_size: usize,
+        tail_block_size: usize,
+        split: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let head_block_size = head_block_size.next_power_of_two();
+        assert!(
+            tail_block_size >= head_block_size,
+            "tail_block_size must be at least head_block_size"
+        );
 
         if max_response_length < impulse_response.len() {
             panic!(
@@ -359,38 +1173,38 @@ impl Convolution for TwoStageFFTConvolver {
         let mut padded_ir = impulse_response.to_vec();
         padded_ir.resize(max_response_length, 0.);
 
-        let head_ir_len = std::cmp::min(max_response_length, tail_block_size);
+        let head_ir_len = std::cmp::min(max_response_length, split);
         let head_convolver = FFTConvolver::init(
             &padded_ir[0..head_ir_len],
             head_block_size,
             max_response_length,
         );
 
-        let tail_convolver0 = (max_response_length > tail_block_size)
-            .then(|| {
-                let tail_ir_len =
-                    std::cmp::min(max_response_length - tail_block_size, tail_block_size);
-                FFTConvolver::init(
-                    &padded_ir[tail_block_size..tail_block_size + tail_ir_len],
-                    head_block_size,
-                    max_response_length,
-                )
-            })
-            .unwrap_or_default();
+        // the first tail block is delayed by `tail_block_size`, so the samples between it and
+        // the split are silent
+        let mut tail_response0 = Vec::with_capacity(tail_block_size);
+        let tail_convolver0 = if max_response_length > split {
+            let tail_ir_len = std::cmp::min(max_response_length, 2 * tail_block_size) - split;
+            tail_response0.resize(split - tail_block_size, 0.);
+            tail_response0.extend_from_slice(&padded_ir[split..split + tail_ir_len]);
+            FFTConvolver::init(&tail_response0, head_block_size, max_response_length)
+        } else {
+            FFTConvolver::default()
+        };
 
         let tail_output0 = vec![0.0; tail_block_size];
         let tail_precalculated0 = vec![0.0; tail_block_size];
 
-        let tail_convolver = (max_response_length > 2 * tail_block_size)
-            .then(|| {
-                let tail_ir_len = max_response_length - 2 * tail_block_size;
-                FFTConvolver::init(
-                    &padded_ir[2 * tail_block_size..2 * tail_block_size + tail_ir_len],
-                    tail_block_size,
-                    max_response_length,
-                )
-            })
-            .unwrap_or_default();
+        let tail_convolver = if max_response_length > 2 * tail_block_size {
+            let tail_ir_len = max_response_length - 2 * tail_block_size;
+            FFTConvolver::init(
+                &padded_ir[2 * tail_block_size..2 * tail_block_size + tail_ir_len],
+                tail_block_size,
+                max_response_length,
+            )
+        } else {
+            FFTConvolver::default()
+        };
 
         let tail_output = vec![0.0; tail_block_size];
         let tail_precalculated = vec![0.0; tail_block_size];
@@ -409,22 +1223,53 @@ impl Convolution for TwoStageFFTConvolver {
             tail_input,
             tail_input_fill,
             precalculated_pos,
+            response_len: impulse_response.len(),
+            head_block_size,
+            tail_block_size,
+            split,
+            tail_response0,
+            tail_flush: TailFlush::default(),
         }
     }
 
-    fn update(&mut self, _response: &[Sample]) {
-        todo!()
+    /// Number of samples at the start of the response that are convolved by the head.
+    pub fn split(&self) -> usize {
+        self.split
     }
 
-    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
-        // Head
-        self.head_convolver.process(input, output);
+    /// Sets the FFT implementation of all stages, see `FFTConvolver::set_fft_backend`.
+    pub fn set_fft_backend(&mut self, backend: FftBackend) {
+        self.head_convolver.set_fft_backend(backend);
+        self.tail_convolver0.set_fft_backend(backend);
+        self.tail_convolver.set_fft_backend(backend);
+    }
+
+    pub fn fft_backend(&self) -> FftBackend {
+        self.head_convolver.fft_backend()
+    }
+
+    /// Like `process`, but writes the convolution with the head of the response, up to the
+    /// split, to `early` and the convolution with the rest to `late`. Their sum is the output of
+    /// `process`.
+    pub fn process_split(&mut self, input: &[Sample], early: &mut [Sample], late: &mut [Sample]) {
+        assert!(
+            input.len() == early.len() && input.len() == late.len(),
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+        self.head_convolver.process(input, early);
+        late.fill(0.);
+        self.process_tail(input, late);
+    }
 
-        // Tail
+    // Adds the output of the tail blocks to `output`
+    fn process_tail(&mut self, input: &[Sample], output: &mut [Sample]) {
         if self.tail_input.is_empty() {
             return;
         }
 
+        let head_block_size = self.head_block_size;
+        let tail_block_size = self.tail_block_size;
         let len = input.len();
         let mut processed = 0;
 
@@ -432,7 +1277,7 @@ impl Convolution for TwoStageFFTConvolver {
             let remaining = len - processed;
             let processing = std::cmp::min(
                 remaining,
-                HEAD_BLOCK_SIZE - (self.tail_input_fill % HEAD_BLOCK_SIZE),
+                head_block_size - (self.tail_input_fill % head_block_size),
             );
 
             // Sum head and tail
@@ -440,20 +1285,20 @@ impl Convolution for TwoStageFFTConvolver {
             let sum_end = processed + processing;
 
             // Sum: 1st tail block
-            if self.tail_precalculated0.len() > 0 {
-                let mut precalculated_pos = self.precalculated_pos;
-                for i in sum_begin..sum_end {
-                    output[i] += self.tail_precalculated0[precalculated_pos];
-                    precalculated_pos += 1;
+            if !self.tail_precalculated0.is_empty() {
+                let precalculated = &self.tail_precalculated0
+                    [self.precalculated_pos..self.precalculated_pos + processing];
+                for (sample, tail) in output[sum_begin..sum_end].iter_mut().zip(precalculated) {
+                    *sample += tail;
                 }
             }
 
             // Sum: 2nd-Nth tail block
-            if self.tail_precalculated.len() > 0 {
-                let mut precalculated_pos = self.precalculated_pos;
-                for i in sum_begin..sum_end {
-                    output[i] += self.tail_precalculated[precalculated_pos];
-                    precalculated_pos += 1;
+            if !self.tail_precalculated.is_empty() {
+                let precalculated = &self.tail_precalculated
+                    [self.precalculated_pos..self.precalculated_pos + processing];
+                for (sample, tail) in output[sum_begin..sum_end].iter_mut().zip(precalculated) {
+                    *sample += tail;
                 }
             }
 
@@ -465,29 +1310,29 @@ impl Convolution for TwoStageFFTConvolver {
             self.tail_input_fill += processing;
 
             // Convolution: 1st tail block
-            if self.tail_precalculated0.len() > 0 && self.tail_input_fill % HEAD_BLOCK_SIZE == 0 {
-                assert!(self.tail_input_fill >= HEAD_BLOCK_SIZE);
-                let block_offset = self.tail_input_fill - HEAD_BLOCK_SIZE;
+            if !self.tail_precalculated0.is_empty() && self.tail_input_fill % head_block_size == 0 {
+                assert!(self.tail_input_fill >= head_block_size);
+                let block_offset = self.tail_input_fill - head_block_size;
                 self.tail_convolver0.process(
-                    &self.tail_input[block_offset..block_offset + HEAD_BLOCK_SIZE],
-                    &mut self.tail_output0[block_offset..block_offset + HEAD_BLOCK_SIZE],
+                    &self.tail_input[block_offset..block_offset + head_block_size],
+                    &mut self.tail_output0[block_offset..block_offset + head_block_size],
                 );
-                if self.tail_input_fill == TAIL_BLOCK_SIZE {
+                if self.tail_input_fill == tail_block_size {
                     std::mem::swap(&mut self.tail_precalculated0, &mut self.tail_output0);
                 }
             }
 
             // Convolution: 2nd-Nth tail block (might be done in some background thread)
-            if self.tail_precalculated.len() > 0
-                && self.tail_input_fill == TAIL_BLOCK_SIZE
-                && self.tail_output.len() == TAIL_BLOCK_SIZE
+            if !self.tail_precalculated.is_empty()
+                && self.tail_input_fill == tail_block_size
+                && self.tail_output.len() == tail_block_size
             {
                 std::mem::swap(&mut self.tail_precalculated, &mut self.tail_output);
                 self.tail_convolver
                     .process(&self.tail_input, &mut self.tail_output);
             }
 
-            if self.tail_input_fill == TAIL_BLOCK_SIZE {
+            if self.tail_input_fill == tail_block_size {
                 self.tail_input_fill = 0;
                 self.precalculated_pos = 0;
             }
@@ -495,4 +1340,137 @@ impl Convolution for TwoStageFFTConvolver {
             processed += processing;
         }
     }
+
+    // the response that is currently applied, the stages are padded to their full length
+    pub(crate) fn response(&self) -> Vec<Sample> {
+        let mut response = self.head_convolver.response();
+        response.extend(
+            self.tail_convolver0
+                .response()
+                .into_iter()
+                .skip(self.split - self.tail_block_size),
+        );
+        response.extend(self.tail_convolver.response());
+        response.resize(self.response_len, 0.);
+        response
+    }
+}
+
+impl Convolution for TwoStageFFTConvolver {
+    // the block size is used for the head, keeping the default ratio to the tail
+    fn init(impulse_response: &[Sample], block_size: usize, max_response_length: usize) -> Self {
+        let head_block_size = block_size.next_power_of_two();
+        Self::new(
+            impulse_response,
+            head_block_size,
+            head_block_size * (TAIL_BLOCK_SIZE / HEAD_BLOCK_SIZE),
+            max_response_length,
+        )
+    }
+
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        if config.latency_mode() != LatencyMode::Zero {
+            return Err(ConfigError::UnsupportedLatencyMode(config.latency_mode()));
+        }
+        let mut convolver = match config.split_point() {
+            Some(split) => Self::with_split(
+                response,
+                config.block_size(),
+                split,
+                config.max_response_length(),
+            ),
+            None => Self::init(response, config.block_size(), config.max_response_length()),
+        };
+        convolver.set_fft_backend(config.fft_backend());
+        Ok(convolver)
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        let response_len = response.len();
+        let tail_block_size = self.tail_block_size;
+
+        if response_len > self.head_convolver.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+
+        let split = self.split;
+        let head_ir_len = std::cmp::min(response_len, split);
+        self.head_convolver.update(&response[0..head_ir_len]);
+
+        let tail_ir_len0 = std::cmp::min(response_len, 2 * tail_block_size).saturating_sub(split);
+        self.tail_response0.clear();
+        if tail_ir_len0 > 0 {
+            self.tail_response0.resize(split - tail_block_size, 0.);
+            self.tail_response0
+                .extend_from_slice(&response[split..split + tail_ir_len0]);
+        }
+        self.tail_convolver0.update(&self.tail_response0);
+
+        let tail_ir_len = response_len.saturating_sub(2 * tail_block_size);
+        self.tail_convolver
+            .update(&response[(2 * tail_block_size).min(response_len)..][..tail_ir_len]);
+
+        // Drop the tail contributions of the previous response
+        self.tail_output0.fill(0.);
+        self.tail_precalculated0.fill(0.);
+        self.tail_output.fill(0.);
+        self.tail_precalculated.fill(0.);
+
+        self.response_len = response_len;
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        self.tail_flush.restart();
+        self.head_convolver.process(input, output);
+        self.process_tail(input, output);
+    }
+
+    fn response_len(&self) -> usize {
+        self.response_len
+    }
+
+    fn reset(&mut self) {
+        self.head_convolver.reset();
+        self.tail_convolver0.reset();
+        self.tail_convolver.reset();
+        self.tail_output0.fill(0.);
+        self.tail_precalculated0.fill(0.);
+        self.tail_output.fill(0.);
+        self.tail_precalculated.fill(0.);
+        self.tail_input.fill(0.);
+        self.tail_input_fill = 0;
+        self.precalculated_pos = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    // the block size is used for the head, keeping the ratio to the tail unless a split was
+    // set with `with_split`, which is kept as long as it is not shorter than the head blocks
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        let response = self.response();
+        let fft_backend = self.fft_backend();
+        let head_block_size = max_block_size.next_power_of_two();
+        *self = if self.split == self.tail_block_size {
+            let tail_block_size = head_block_size * (self.tail_block_size / self.head_block_size);
+            Self::new(
+                &response,
+                head_block_size,
+                tail_block_size,
+                max_response_length,
+            )
+        } else {
+            Self::with_split(
+                &response,
+                head_block_size,
+                std::cmp::max(self.split, head_block_size),
+                max_response_length,
+            )
+        };
+        self.set_fft_backend(fft_backend);
+    }
 }
@@ -0,0 +1,124 @@
+use crate::multi_channel_convolver::MultiChannelConvolver;
+use crate::sample_format::SampleFormat;
+use crate::Sample;
+
+/// Front end of a `MultiChannelConvolver` for interleaved frames and integer sample formats.
+///
+/// Buffers are converted and deinterleaved into preallocated planar scratch, so processing stays
+/// real-time safe. Buffers longer than `max_frames` are processed in several chunks.
+#[derive(Clone)]
+pub struct InterleavedConvolver {
+    convolver: MultiChannelConvolver,
+    max_frames: usize,
+    input: Vec<Vec<Sample>>,
+    output: Vec<Vec<Sample>>,
+}
+
+impl InterleavedConvolver {
+    pub fn new(convolver: MultiChannelConvolver, max_frames: usize) -> Self {
+        assert!(max_frames > 0, "max_frames must be greater than 0");
+        let channel_count = convolver.channel_count();
+        Self {
+            convolver,
+            max_frames,
+            input: (0..channel_count)
+                .map(|_| Vec::with_capacity(max_frames))
+                .collect(),
+            output: (0..channel_count)
+                .map(|_| Vec::with_capacity(max_frames))
+                .collect(),
+        }
+    }
+
+    pub fn convolver(&self) -> &MultiChannelConvolver {
+        &self.convolver
+    }
+
+    pub fn convolver_mut(&mut self) -> &mut MultiChannelConvolver {
+        &mut self.convolver
+    }
+
+    /// Convolves interleaved frames, e.g. `L R L R ...` for two channels.
+    pub fn process<T: SampleFormat>(&mut self, input: &[T], output: &mut [T]) {
+        let channel_count = self.input.len();
+        assert_eq!(
+            input.len(),
+            output.len(),
+            "input and output must have the same length"
+        );
+        if channel_count == 0 {
+            return;
+        }
+        assert_eq!(
+            input.len() % channel_count,
+            0,
+            "buffers must contain whole frames"
+        );
+
+        let chunk_len = self.max_frames * channel_count;
+        for (input, output) in input.chunks(chunk_len).zip(output.chunks_mut(chunk_len)) {
+            let frames = input.len() / channel_count;
+            for (channel, buffer) in self.input.iter_mut().enumerate() {
+                buffer.clear();
+                buffer.extend(
+                    input[channel..]
+                        .iter()
+                        .step_by(channel_count)
+                        .map(|sample| sample.to_sample()),
+                );
+            }
+
+            self.process_scratch(frames);
+
+            for (channel, buffer) in self.output.iter().enumerate() {
+                for (sample, converted) in output[channel..]
+                    .iter_mut()
+                    .step_by(channel_count)
+                    .zip(buffer)
+                {
+                    *sample = T::from_sample(*converted);
+                }
+            }
+        }
+    }
+
+    /// Convolves planar buffers of any sample format, one slice per channel.
+    pub fn process_planar<T: SampleFormat>(&mut self, input: &[&[T]], output: &mut [&mut [T]]) {
+        assert_eq!(input.len(), self.input.len(), "one input per channel");
+        assert_eq!(output.len(), self.output.len(), "one output per channel");
+        let len = input.first().map_or(0, |input| input.len());
+        assert!(
+            input.iter().all(|input| input.len() == len)
+                && output.iter().all(|output| output.len() == len),
+            "all buffers must have the same length"
+        );
+
+        let mut processed = 0;
+        while processed < len {
+            let frames = std::cmp::min(len - processed, self.max_frames);
+            let chunk = processed..processed + frames;
+            for (buffer, input) in self.input.iter_mut().zip(input) {
+                buffer.clear();
+                buffer.extend(input[chunk.clone()].iter().map(|sample| sample.to_sample()));
+            }
+
+            self.process_scratch(frames);
+
+            for (buffer, output) in self.output.iter().zip(output.iter_mut()) {
+                for (sample, converted) in output[chunk.clone()].iter_mut().zip(buffer) {
+                    *sample = T::from_sample(*converted);
+                }
+            }
+            processed += frames;
+        }
+    }
+
+    // Convolves the input scratch into the output scratch, within their capacity
+    fn process_scratch(&mut self, frames: usize) {
+        for buffer in &mut self.output {
+            buffer.clear();
+            buffer.resize(frames, 0.);
+        }
+        self.convolver.process(&self.input, &mut self.output);
+    }
+}
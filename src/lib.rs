@@ -1,6 +1,22 @@
+pub mod auto_convolver;
+pub mod batched_convolver;
+pub mod bypass_convolver;
+pub mod config;
+pub mod convolution_reverb;
 pub mod crossfade_convolver;
+pub mod direct_convolver;
 pub mod fft_convolver;
+pub mod interleaved_convolver;
+pub mod matrix_convolver;
+pub mod multi_channel_convolver;
+pub mod one_to_many_convolver;
+pub mod overlap_save_convolver;
+pub mod sample_format;
 mod tests;
+pub mod true_stereo_convolver;
+
+use config::{ConfigError, ConvolverConfig};
+use fft_convolver::LatencyMode;
 
 // todo: use a generic floating point type
 pub type Sample = f32;
@@ -8,8 +24,256 @@ pub type Sample = f32;
 pub trait Convolution: Clone {
     fn init(response: &[Sample], max_block_size: usize, max_response_length: usize) -> Self;
 
+    // called by `ConvolverConfig::build` with a response that fits the configuration,
+    // settings the convolver cannot honour are reported as error
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        if config.latency_mode() != LatencyMode::Zero {
+            return Err(ConfigError::UnsupportedLatencyMode(config.latency_mode()));
+        }
+        Ok(Self::init(
+            response,
+            config.block_size(),
+            config.max_response_length(),
+        ))
+    }
+
     // must be implemented in a real-time safe way, e.g. no heap allocations
     fn update(&mut self, response: &[Sample]);
 
     fn process(&mut self, input: &[Sample], output: &mut [Sample]);
+
+    // delay of the output relative to the input in samples
+    fn latency(&self) -> usize {
+        0
+    }
+
+    // length of the response that is currently applied
+    fn response_len(&self) -> usize;
+
+    // number of samples the output may still be non-zero after the input went silent
+    fn tail_samples(&self) -> usize {
+        self.latency() + self.response_len().saturating_sub(1)
+    }
+
+    // clears all processing history, must be implemented in a real-time safe way
+    fn reset(&mut self);
+
+    // writes the output that is still due after the last processed input, as if silence
+    // followed it, and fills the rest of `output` with zeros. Returns the number of tail
+    // samples written, which is 0 once the tail is exhausted.
+    fn flush(&mut self, output: &mut [Sample]) -> usize;
+
+    // changes the limits given to `init`, keeping the response and all other settings. The
+    // processing history is cleared. Not real-time safe, the buffers are reallocated.
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize);
+}
+
+// Progress of draining the tail with `flush`, restarted by every call to `process`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TailFlush {
+    flushed: usize,
+}
+
+impl Default for TailFlush {
+    // nothing has been processed yet, so there is no tail
+    fn default() -> Self {
+        Self {
+            flushed: usize::MAX,
+        }
+    }
+}
+
+impl TailFlush {
+    pub(crate) fn restart(&mut self) {
+        self.flushed = 0;
+    }
+
+    pub(crate) fn remaining(&self, tail_samples: usize) -> usize {
+        tail_samples.saturating_sub(self.flushed)
+    }
+
+    pub(crate) fn advance(&mut self, flushed: usize) {
+        self.flushed = self.flushed.saturating_add(flushed);
+    }
+}
+
+// Response update that is due after a number of processed samples
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ScheduledUpdate {
+    response: Vec<Sample>,
+    // samples until the update is due, counted from the next processed sample
+    offset: Option<usize>,
+}
+
+impl ScheduledUpdate {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            response: Vec::with_capacity(capacity),
+            offset: None,
+        }
+    }
+
+    // makes room for responses of up to `capacity` samples
+    pub(crate) fn reserve(&mut self, capacity: usize) {
+        if self.response.capacity() < capacity {
+            self.response.reserve_exact(capacity - self.response.len());
+        }
+    }
+
+    // replaces a previously scheduled update, only allocates beyond the reserved capacity
+    pub(crate) fn schedule(&mut self, response: &[Sample], sample_offset: usize) {
+        self.response.clear();
+        self.response.extend_from_slice(response);
+        self.offset = Some(sample_offset);
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.offset = None;
+    }
+
+    pub(crate) fn is_scheduled(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    // Returns the number of samples to process before the update, if it is due within the next
+    // `len` samples. Otherwise the schedule moves on by `len` samples.
+    pub(crate) fn due_within(&mut self, len: usize) -> Option<usize> {
+        match self.offset {
+            Some(offset) if offset < len => Some(offset),
+            Some(offset) => {
+                self.offset = Some(offset - len);
+                None
+            }
+            None => None,
+        }
+    }
+
+    // hands out the response of the due update, which should be given back with `restore`
+    pub(crate) fn take(&mut self) -> Vec<Sample> {
+        self.offset = None;
+        std::mem::take(&mut self.response)
+    }
+
+    pub(crate) fn restore(&mut self, response: Vec<Sample>) {
+        self.response = response;
+    }
+}
+
+pub(crate) const SILENCE: [Sample; 256] = [0.; 256];
+
+// Processes silence to write the remaining tail of `convolver`, returns the updated progress
+// and the number of tail samples written
+pub(crate) fn flush_tail<T: Convolution>(
+    convolver: &mut T,
+    mut progress: TailFlush,
+    output: &mut [Sample],
+) -> (TailFlush, usize) {
+    let written = std::cmp::min(progress.remaining(convolver.tail_samples()), output.len());
+    let (tail, rest) = output.split_at_mut(written);
+    for chunk in tail.chunks_mut(SILENCE.len()) {
+        convolver.process(&SILENCE[..chunk.len()], chunk);
+    }
+    rest.fill(0.);
+    progress.advance(written);
+    (progress, written)
+}
+
+// Object-safe counterpart of `Convolution`, which allows to keep convolvers of different types
+// as `Box<dyn DynConvolution>`. It is implemented for every `Convolution`. Its methods carry a
+// `dyn_` prefix, so calls stay unambiguous with both traits in scope.
+pub trait DynConvolution: Send {
+    // must be implemented in a real-time safe way, e.g. no heap allocations
+    fn dyn_update(&mut self, response: &[Sample]);
+
+    fn dyn_process(&mut self, input: &[Sample], output: &mut [Sample]);
+
+    fn dyn_latency(&self) -> usize;
+
+    fn dyn_response_len(&self) -> usize;
+
+    fn dyn_tail_samples(&self) -> usize;
+
+    fn dyn_reset(&mut self);
+
+    fn dyn_flush(&mut self, output: &mut [Sample]) -> usize;
+
+    fn dyn_reconfigure(&mut self, max_block_size: usize, max_response_length: usize);
+
+    fn clone_box(&self) -> Box<dyn DynConvolution>;
+}
+
+impl<T: Convolution + Send + 'static> DynConvolution for T {
+    fn dyn_update(&mut self, response: &[Sample]) {
+        Convolution::update(self, response);
+    }
+
+    fn dyn_process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        Convolution::process(self, input, output);
+    }
+
+    fn dyn_latency(&self) -> usize {
+        Convolution::latency(self)
+    }
+
+    fn dyn_response_len(&self) -> usize {
+        Convolution::response_len(self)
+    }
+
+    fn dyn_tail_samples(&self) -> usize {
+        Convolution::tail_samples(self)
+    }
+
+    fn dyn_reset(&mut self) {
+        Convolution::reset(self);
+    }
+
+    fn dyn_flush(&mut self, output: &mut [Sample]) -> usize {
+        Convolution::flush(self, output)
+    }
+
+    fn dyn_reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        Convolution::reconfigure(self, max_block_size, max_response_length);
+    }
+
+    fn clone_box(&self) -> Box<dyn DynConvolution> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn DynConvolution> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Object-safe interface of the convolvers with several channels in planar buffers, one slice
+// per input or output channel of the same length. Updates are not part of it, since the layout
+// of the responses depends on the convolver.
+pub trait PlanarConvolution: Send {
+    fn input_count(&self) -> usize;
+
+    fn output_count(&self) -> usize;
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]);
+
+    fn latency(&self) -> usize {
+        0
+    }
+
+    // number of samples any output may still be non-zero after all inputs went silent
+    fn tail_samples(&self) -> usize;
+
+    // clears all processing history, must be implemented in a real-time safe way
+    fn reset(&mut self);
+
+    // writes the remaining tail of every output like `Convolution::flush`
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize;
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution>;
+}
+
+impl Clone for Box<dyn PlanarConvolution> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
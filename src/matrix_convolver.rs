@@ -0,0 +1,408 @@
+use rustfft::num_complex::Complex;
+
+use crate::fft_convolver::{complex_size, copy_and_pad, sum, Fft};
+use crate::multi_channel_convolver::ResponseSpectra;
+use crate::{PlanarConvolution, Sample, TailFlush, SILENCE};
+
+// Input history of one input, one segment longer than the responses so the previous block can
+// be convolved again after an update
+#[derive(Clone)]
+struct InputState {
+    segments: Vec<Vec<Complex<f32>>>,
+    input_buffer: Vec<f32>,
+}
+
+// Processing state of one output
+#[derive(Clone)]
+struct OutputState {
+    pre_multiplied: Vec<Complex<f32>>,
+    overlap: Vec<f32>,
+}
+
+/// Uniformly partitioned convolution matrix from several inputs to several outputs, with one
+/// response per route.
+///
+/// Every input is transformed once per block, and the contributions of all inputs to an output
+/// are summed in the frequency domain, so each output needs a single inverse FFT. Routes with
+/// an empty response are skipped, as are outputs without any connected route. All routes have
+/// spectra allocated up to the max response length, so any route can be set later without
+/// allocating. Updates apply the new responses to the earlier input as well, as if they had
+/// been connected all along, so the routes that keep their response continue seamlessly.
+#[derive(Clone)]
+pub struct MatrixConvolver {
+    max_response_length: usize,
+    block_size: usize,
+    seg_count: usize,
+    input_count: usize,
+    // one row of `input_count` routes per output
+    routes: Vec<ResponseSpectra>,
+    inputs: Vec<InputState>,
+    outputs: Vec<OutputState>,
+    fft: Fft,
+    fft_buffer: Vec<f32>,
+    conv: Vec<Complex<f32>>,
+    current: usize,
+    input_buffer_fill: usize,
+    tail_flush: TailFlush,
+}
+
+impl MatrixConvolver {
+    /// Creates a convolver with `input_count` inputs and one output per row of `responses`,
+    /// every row holds one response per input. `responses[output][input]` is applied to the
+    /// route from `input` to `output`, an empty response leaves it unconnected.
+    pub fn new(
+        responses: &[&[&[Sample]]],
+        input_count: usize,
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let output_count = responses.len();
+        let block_size = block_size.next_power_of_two();
+        let seg_size = 2 * block_size;
+        let seg_count = (max_response_length as f64 / block_size as f64).ceil() as usize;
+        let fft_complex_size = complex_size(seg_size);
+
+        let mut fft = Fft::default();
+        fft.init(seg_size);
+
+        let input = InputState {
+            segments: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count + 1],
+            input_buffer: vec![0.; block_size],
+        };
+        let output = OutputState {
+            pre_multiplied: vec![Complex::new(0., 0.); fft_complex_size],
+            overlap: vec![0.; block_size],
+        };
+
+        let mut convolver = Self {
+            max_response_length,
+            block_size,
+            seg_count,
+            input_count,
+            routes: vec![
+                ResponseSpectra::new(seg_count, fft_complex_size);
+                input_count * output_count
+            ],
+            inputs: vec![input; input_count],
+            outputs: vec![output; output_count],
+            fft,
+            fft_buffer: vec![0.; seg_size],
+            conv: vec![Complex::new(0., 0.); fft_complex_size],
+            current: 0,
+            input_buffer_fill: 0,
+            tail_flush: TailFlush::default(),
+        };
+        convolver.update(responses);
+        convolver
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Replaces the responses of all routes at once, laid out like in `new`. Real-time safe.
+    ///
+    /// Every output is convolved again with the new responses for the current block, which
+    /// costs about as much as processing a block.
+    pub fn update(&mut self, responses: &[&[&[Sample]]]) {
+        assert_eq!(
+            responses.len(),
+            self.outputs.len(),
+            "one row of responses per output"
+        );
+        assert!(
+            responses.iter().all(|row| row.len() == self.input_count),
+            "one response per input in every row"
+        );
+        for (output, row) in responses.iter().enumerate() {
+            for (input, response) in row.iter().enumerate() {
+                self.prepare_route(input, output, response);
+            }
+            self.rebuild_output(output);
+        }
+    }
+
+    /// Replaces the response of the route from `input` to `output`, an empty response
+    /// disconnects it. The other routes of `output` are not affected. Real-time safe.
+    ///
+    /// `output` is convolved again with its responses for the current block, which costs
+    /// about as much as processing a block for one output.
+    pub fn update_route(&mut self, input: usize, output: usize, response: &[Sample]) {
+        self.prepare_route(input, output, response);
+        self.rebuild_output(output);
+    }
+
+    /// Convolves planar buffers, one input slice per input and one output slice per output,
+    /// all of the same length.
+    pub fn process<I: AsRef<[Sample]>, O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[I],
+        output: &mut [O],
+    ) {
+        assert_eq!(input.len(), self.input_count, "one input slice per input");
+        assert_eq!(
+            output.len(),
+            self.outputs.len(),
+            "one output slice per output"
+        );
+        let len = input.first().map_or(0, |input| input.as_ref().len());
+        assert!(
+            input.iter().all(|input| input.as_ref().len() == len)
+                && output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        if self.seg_count == 0 || self.input_count == 0 {
+            for output in output.iter_mut() {
+                output.as_mut().fill(0.);
+            }
+            return;
+        }
+
+        let mut processed = 0;
+        while processed < len {
+            let processing =
+                std::cmp::min(len - processed, self.block_size - self.input_buffer_fill);
+            let chunk = processed..processed + processing;
+            for (state, input) in self.inputs.iter_mut().zip(input) {
+                state.input_buffer[self.input_buffer_fill..self.input_buffer_fill + processing]
+                    .copy_from_slice(&input.as_ref()[chunk.clone()]);
+            }
+            self.convolve_chunk(output, chunk.start, processing);
+            processed += processing;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        0
+    }
+
+    /// Length of the response of the route from `input` to `output`.
+    pub fn response_len(&self, input: usize, output: usize) -> usize {
+        self.routes[self.route_index(input, output)].response_len
+    }
+
+    /// Number of samples any output may still be non-zero after all inputs went silent.
+    pub fn tail_samples(&self) -> usize {
+        self.routes
+            .iter()
+            .map(|route| route.response_len.saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Clears all processing history. Real-time safe.
+    pub fn reset(&mut self) {
+        for input in &mut self.inputs {
+            for segment in &mut input.segments {
+                segment.fill(Complex::new(0., 0.));
+            }
+            input.input_buffer.fill(0.);
+        }
+        for output in &mut self.outputs {
+            output.pre_multiplied.fill(Complex::new(0., 0.));
+            output.overlap.fill(0.);
+        }
+        self.current = 0;
+        self.input_buffer_fill = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of every output like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        assert_eq!(
+            output.len(),
+            self.outputs.len(),
+            "one output slice per output"
+        );
+        let len = output.first_mut().map_or(0, |output| output.as_mut().len());
+        assert!(
+            output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let mut processed = 0;
+        while processed < written {
+            let processing = std::cmp::min(
+                std::cmp::min(written - processed, SILENCE.len()),
+                self.block_size - self.input_buffer_fill,
+            );
+            for state in &mut self.inputs {
+                state.input_buffer[self.input_buffer_fill..self.input_buffer_fill + processing]
+                    .copy_from_slice(&SILENCE[..processing]);
+            }
+            self.convolve_chunk(output, processed, processing);
+            processed += processing;
+        }
+        for output in output.iter_mut() {
+            output.as_mut()[written..].fill(0.);
+        }
+        self.tail_flush.advance(written);
+        written
+    }
+
+    fn route_index(&self, input: usize, output: usize) -> usize {
+        assert!(input < self.input_count, "input out of range");
+        output * self.input_count + input
+    }
+
+    fn prepare_route(&mut self, input: usize, output: usize, response: &[Sample]) {
+        if response.len() > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+        let index = self.route_index(input, output);
+        self.routes[index].prepare(response, self.block_size, &self.fft, &mut self.fft_buffer);
+    }
+
+    // Recomputes the overlap of the previous block and the products of the current block from
+    // the input history, with the responses that are now connected to `output`
+    fn rebuild_output(&mut self, output: usize) {
+        let block_size = self.block_size;
+        let history_len = self.seg_count + 1;
+        let routes = &self.routes[output * self.input_count..(output + 1) * self.input_count];
+        let state = &mut self.outputs[output];
+
+        let previous = (self.current + 1) % history_len;
+        self.conv.fill(Complex::new(0., 0.));
+        for (route, input) in routes.iter().zip(&self.inputs) {
+            route.multiply_history(&mut self.conv, &input.segments, previous);
+            route.multiply_latest(&mut self.conv, &input.segments[previous]);
+        }
+        self.fft
+            .inverse(&mut self.conv, &mut self.fft_buffer)
+            .unwrap();
+        state
+            .overlap
+            .copy_from_slice(&self.fft_buffer[block_size..2 * block_size]);
+
+        state.pre_multiplied.fill(Complex::new(0., 0.));
+        for (route, input) in routes.iter().zip(&self.inputs) {
+            route.multiply_history(&mut state.pre_multiplied, &input.segments, self.current);
+        }
+    }
+
+    // Convolves `processing` samples that were written to the input buffers and do not reach
+    // beyond the current block, starting at `offset` of the output slices
+    fn convolve_chunk<O: AsMut<[Sample]>>(
+        &mut self,
+        output: &mut [O],
+        offset: usize,
+        processing: usize,
+    ) {
+        let block_size = self.block_size;
+        let input_buffer_pos = self.input_buffer_fill;
+
+        // Forward FFT, once per input
+        for state in &mut self.inputs {
+            copy_and_pad(&mut self.fft_buffer, &state.input_buffer, block_size);
+            self.fft
+                .forward(&mut self.fft_buffer, &mut state.segments[self.current])
+                .unwrap();
+        }
+
+        for ((routes, state), output) in self
+            .routes
+            .chunks(self.input_count)
+            .zip(&mut self.outputs)
+            .zip(output.iter_mut())
+        {
+            // an output without routes is silent, its state is rebuilt once it is connected again
+            if routes
+                .iter()
+                .all(|route| route.active_partitions.is_empty())
+            {
+                output.as_mut()[offset..offset + processing].fill(0.);
+                continue;
+            }
+
+            // complex multiplication, summed over all inputs
+            if input_buffer_pos == 0 {
+                state.pre_multiplied.fill(Complex::new(0., 0.));
+                for (route, input) in routes.iter().zip(&self.inputs) {
+                    route.multiply_history(
+                        &mut state.pre_multiplied,
+                        &input.segments,
+                        self.current,
+                    );
+                }
+            }
+            self.conv.copy_from_slice(&state.pre_multiplied);
+            for (route, input) in routes.iter().zip(&self.inputs) {
+                route.multiply_latest(&mut self.conv, &input.segments[self.current]);
+            }
+
+            // Backward FFT
+            self.fft
+                .inverse(&mut self.conv, &mut self.fft_buffer)
+                .unwrap();
+
+            // Add overlap
+            sum(
+                &mut output.as_mut()[offset..offset + processing],
+                &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
+                &state.overlap[input_buffer_pos..input_buffer_pos + processing],
+            );
+
+            // Save the overlap once the block is complete
+            if input_buffer_pos + processing == block_size {
+                state
+                    .overlap
+                    .copy_from_slice(&self.fft_buffer[block_size..2 * block_size]);
+            }
+        }
+
+        // Move on to the next segment once the block is complete
+        self.input_buffer_fill += processing;
+        if self.input_buffer_fill == block_size {
+            for state in &mut self.inputs {
+                state.input_buffer.fill(0.);
+            }
+            self.input_buffer_fill = 0;
+            self.current = if self.current > 0 {
+                self.current - 1
+            } else {
+                self.seg_count
+            };
+        }
+    }
+}
+
+impl PlanarConvolution for MatrixConvolver {
+    fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]) {
+        MatrixConvolver::process(self, input, output);
+    }
+
+    fn tail_samples(&self) -> usize {
+        MatrixConvolver::tail_samples(self)
+    }
+
+    fn reset(&mut self) {
+        MatrixConvolver::reset(self);
+    }
+
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize {
+        MatrixConvolver::flush(self, output)
+    }
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution> {
+        Box::new(self.clone())
+    }
+}
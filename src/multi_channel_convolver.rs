@@ -0,0 +1,421 @@
+use rustfft::num_complex::Complex;
+
+use crate::fft_convolver::{
+    complex_multiply_accumulate, complex_size, copy_and_pad, is_silent, sum, Fft,
+};
+use crate::{PlanarConvolution, Sample, TailFlush, SILENCE};
+
+// Spectra of the partitions of one response
+#[derive(Clone)]
+pub(crate) struct ResponseSpectra {
+    pub(crate) segments_ir: Vec<Vec<Complex<f32>>>,
+    // ascending indices of the partitions that are not all zero
+    pub(crate) active_partitions: Vec<usize>,
+    pub(crate) response_len: usize,
+}
+
+impl ResponseSpectra {
+    pub(crate) fn new(seg_count: usize, fft_complex_size: usize) -> Self {
+        Self {
+            segments_ir: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count],
+            active_partitions: Vec::with_capacity(seg_count),
+            response_len: 0,
+        }
+    }
+
+    // Transforms the partitions of `response`, which must fit into the allocated segments
+    pub(crate) fn prepare(
+        &mut self,
+        response: &[Sample],
+        block_size: usize,
+        fft: &Fft,
+        fft_buffer: &mut [f32],
+    ) {
+        let response_len = response.len();
+        self.response_len = response_len;
+        self.active_partitions.clear();
+        for (i, segment) in self.segments_ir.iter_mut().enumerate() {
+            let offset = std::cmp::min(i * block_size, response_len);
+            let partition = &response[offset..std::cmp::min(offset + block_size, response_len)];
+            if is_silent(partition, 0.) {
+                segment.fill(Complex::new(0., 0.));
+                continue;
+            }
+            copy_and_pad(fft_buffer, partition, partition.len());
+            fft.forward(fft_buffer, segment).unwrap();
+            self.active_partitions.push(i);
+        }
+    }
+
+    // Adds the products of the partitions with a lag of at least one, `current` is the index
+    // of the latest input segment
+    pub(crate) fn multiply_history(
+        &self,
+        result: &mut [Complex<f32>],
+        segments: &[Vec<Complex<f32>>],
+        current: usize,
+    ) {
+        for &i in self.active_partitions.iter().skip_while(|&&i| i == 0) {
+            let index_audio = (current + i) % segments.len();
+            complex_multiply_accumulate(result, &self.segments_ir[i], &segments[index_audio]);
+        }
+    }
+
+    // Adds the product of the latest input segment and the first partition
+    pub(crate) fn multiply_latest(&self, result: &mut [Complex<f32>], segment: &[Complex<f32>]) {
+        if self.active_partitions.first() == Some(&0) {
+            complex_multiply_accumulate(result, segment, &self.segments_ir[0]);
+        }
+    }
+}
+
+// Processing state of one channel
+#[derive(Clone)]
+struct ChannelState {
+    segments: Vec<Vec<Complex<f32>>>,
+    pre_multiplied: Vec<Complex<f32>>,
+    overlap: Vec<f32>,
+    input_buffer: Vec<f32>,
+}
+
+/// Uniformly partitioned convolution of several channels in planar buffers.
+///
+/// All channels run in lockstep, so they share the block position, the FFT and its scratch
+/// buffers. Channels either share one response, see `new`, or have one response each, see
+/// `with_responses`. Updates are linked: they apply to all channels at the same sample and,
+/// like the `FFTConvolver` in `UpdateMode::Reset`, drop the tail of the previous responses.
+#[derive(Clone)]
+pub struct MultiChannelConvolver {
+    max_response_length: usize,
+    block_size: usize,
+    seg_count: usize,
+    // a single entry is shared by all channels
+    responses: Vec<ResponseSpectra>,
+    channels: Vec<ChannelState>,
+    fft: Fft,
+    fft_buffer: Vec<f32>,
+    conv: Vec<Complex<f32>>,
+    current: usize,
+    input_buffer_fill: usize,
+    tail_flush: TailFlush,
+}
+
+impl MultiChannelConvolver {
+    /// Creates a convolver that applies the same response to `channel_count` channels.
+    pub fn new(
+        response: &[Sample],
+        channel_count: usize,
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let mut convolver = Self::allocate(channel_count, 1, block_size, max_response_length);
+        convolver.update(response);
+        convolver
+    }
+
+    /// Creates a convolver with one channel per response.
+    pub fn with_responses(
+        responses: &[&[Sample]],
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let mut convolver = Self::allocate(
+            responses.len(),
+            responses.len(),
+            block_size,
+            max_response_length,
+        );
+        convolver.update_channels(responses);
+        convolver
+    }
+
+    fn allocate(
+        channel_count: usize,
+        response_count: usize,
+        block_size: usize,
+        max_response_length: usize,
+    ) -> Self {
+        let block_size = block_size.next_power_of_two();
+        let seg_size = 2 * block_size;
+        let seg_count = (max_response_length as f64 / block_size as f64).ceil() as usize;
+        let fft_complex_size = complex_size(seg_size);
+
+        let mut fft = Fft::default();
+        fft.init(seg_size);
+
+        let response = ResponseSpectra::new(seg_count, fft_complex_size);
+        let channel = ChannelState {
+            segments: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count],
+            pre_multiplied: vec![Complex::new(0., 0.); fft_complex_size],
+            overlap: vec![0.; block_size],
+            input_buffer: vec![0.; block_size],
+        };
+
+        Self {
+            max_response_length,
+            block_size,
+            seg_count,
+            responses: vec![response; response_count],
+            channels: vec![channel; channel_count],
+            fft,
+            fft_buffer: vec![0.; seg_size],
+            conv: vec![Complex::new(0., 0.); fft_complex_size],
+            current: 0,
+            input_buffer_fill: 0,
+            tail_flush: TailFlush::default(),
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Applies `response` to all channels. Real-time safe.
+    pub fn update(&mut self, response: &[Sample]) {
+        for index in 0..self.responses.len() {
+            self.prepare_response(index, response);
+        }
+        self.clear_tails();
+    }
+
+    /// Applies one response per channel at once. Real-time safe.
+    ///
+    /// Panics if the convolver shares one response between its channels.
+    pub fn update_channels(&mut self, responses: &[&[Sample]]) {
+        assert_eq!(
+            responses.len(),
+            self.responses.len(),
+            "one response per channel requires a convolver created with `with_responses`"
+        );
+        for (index, response) in responses.iter().enumerate() {
+            self.prepare_response(index, response);
+        }
+        self.clear_tails();
+    }
+
+    /// Convolves planar buffers, one slice per channel of the same length.
+    ///
+    /// Besides `&[&[Sample]]` and `&mut [&mut [Sample]]`, channels may be passed as `Vec`s.
+    pub fn process<I: AsRef<[Sample]>, O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[I],
+        output: &mut [O],
+    ) {
+        assert_eq!(input.len(), self.channels.len(), "one input per channel");
+        assert_eq!(output.len(), self.channels.len(), "one output per channel");
+        let len = input.first().map_or(0, |input| input.as_ref().len());
+        assert!(
+            input.iter().all(|input| input.as_ref().len() == len)
+                && output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        if self.seg_count == 0 {
+            for output in output.iter_mut() {
+                output.as_mut().fill(0.);
+            }
+            return;
+        }
+
+        let mut processed = 0;
+        while processed < len {
+            let processing =
+                std::cmp::min(len - processed, self.block_size - self.input_buffer_fill);
+            let chunk = processed..processed + processing;
+            for (channel, (input, output)) in input.iter().zip(output.iter_mut()).enumerate() {
+                self.convolve_channel(
+                    channel,
+                    &input.as_ref()[chunk.clone()],
+                    &mut output.as_mut()[chunk.clone()],
+                );
+            }
+            self.advance(processing);
+            processed += processing;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        0
+    }
+
+    pub fn response_len(&self, channel: usize) -> usize {
+        self.responses[self.response_index(channel)].response_len
+    }
+
+    /// Number of samples the output of any channel may still be non-zero after the input went
+    /// silent.
+    pub fn tail_samples(&self) -> usize {
+        self.responses
+            .iter()
+            .map(|response| response.response_len.saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Clears all processing history. Real-time safe.
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            for segment in &mut channel.segments {
+                segment.fill(Complex::new(0., 0.));
+            }
+            channel.pre_multiplied.fill(Complex::new(0., 0.));
+            channel.overlap.fill(0.);
+            channel.input_buffer.fill(0.);
+        }
+        self.current = 0;
+        self.input_buffer_fill = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of every channel like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        assert_eq!(output.len(), self.channels.len(), "one output per channel");
+        let len = output.first_mut().map_or(0, |output| output.as_mut().len());
+        assert!(
+            output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let mut processed = 0;
+        while processed < written {
+            let processing = std::cmp::min(
+                std::cmp::min(written - processed, SILENCE.len()),
+                self.block_size - self.input_buffer_fill,
+            );
+            let chunk = processed..processed + processing;
+            for (channel, output) in output.iter_mut().enumerate() {
+                self.convolve_channel(
+                    channel,
+                    &SILENCE[..processing],
+                    &mut output.as_mut()[chunk.clone()],
+                );
+            }
+            self.advance(processing);
+            processed += processing;
+        }
+        for output in output.iter_mut() {
+            output.as_mut()[written..].fill(0.);
+        }
+        self.tail_flush.advance(written);
+        written
+    }
+
+    fn response_index(&self, channel: usize) -> usize {
+        if self.responses.len() == 1 {
+            0
+        } else {
+            channel
+        }
+    }
+
+    fn prepare_response(&mut self, index: usize, response: &[Sample]) {
+        let response_len = response.len();
+        if response_len > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+
+        self.responses[index].prepare(response, self.block_size, &self.fft, &mut self.fft_buffer);
+    }
+
+    // the previous responses stop contributing, the input history is kept
+    fn clear_tails(&mut self) {
+        for channel in &mut self.channels {
+            channel.pre_multiplied.fill(Complex::new(0., 0.));
+            channel.overlap.fill(0.);
+        }
+    }
+
+    // Convolves a chunk that does not reach beyond the current block
+    fn convolve_channel(&mut self, channel: usize, input: &[Sample], output: &mut [Sample]) {
+        let block_size = self.block_size;
+        let input_buffer_pos = self.input_buffer_fill;
+        let processing = input.len();
+        let response = &self.responses[self.response_index(channel)];
+        let state = &mut self.channels[channel];
+
+        state.input_buffer[input_buffer_pos..input_buffer_pos + processing].copy_from_slice(input);
+
+        // Forward FFT
+        copy_and_pad(&mut self.fft_buffer, &state.input_buffer, block_size);
+        self.fft
+            .forward(&mut self.fft_buffer, &mut state.segments[self.current])
+            .unwrap();
+
+        // complex multiplication
+        if input_buffer_pos == 0 {
+            state.pre_multiplied.fill(Complex::new(0., 0.));
+            response.multiply_history(&mut state.pre_multiplied, &state.segments, self.current);
+        }
+        self.conv.copy_from_slice(&state.pre_multiplied);
+        response.multiply_latest(&mut self.conv, &state.segments[self.current]);
+
+        // Backward FFT
+        self.fft
+            .inverse(&mut self.conv, &mut self.fft_buffer)
+            .unwrap();
+
+        // Add overlap
+        sum(
+            output,
+            &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
+            &state.overlap[input_buffer_pos..input_buffer_pos + processing],
+        );
+
+        // Save the overlap once the block is complete
+        if input_buffer_pos + processing == block_size {
+            state
+                .overlap
+                .copy_from_slice(&self.fft_buffer[block_size..2 * block_size]);
+            state.input_buffer.fill(0.);
+        }
+    }
+
+    // Moves on to the next segment once all channels completed the block
+    fn advance(&mut self, processed: usize) {
+        self.input_buffer_fill += processed;
+        if self.input_buffer_fill == self.block_size {
+            self.input_buffer_fill = 0;
+            self.current = if self.current > 0 {
+                self.current - 1
+            } else {
+                self.seg_count - 1
+            };
+        }
+    }
+}
+
+impl PlanarConvolution for MultiChannelConvolver {
+    fn input_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    fn output_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]) {
+        MultiChannelConvolver::process(self, input, output);
+    }
+
+    fn tail_samples(&self) -> usize {
+        MultiChannelConvolver::tail_samples(self)
+    }
+
+    fn reset(&mut self) {
+        MultiChannelConvolver::reset(self);
+    }
+
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize {
+        MultiChannelConvolver::flush(self, output)
+    }
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,292 @@
+use rustfft::num_complex::Complex;
+
+use crate::fft_convolver::{complex_size, copy_and_pad, sum, Fft};
+use crate::multi_channel_convolver::ResponseSpectra;
+use crate::{PlanarConvolution, Sample, TailFlush, SILENCE};
+
+// Processing state of one output
+#[derive(Clone)]
+struct OutputState {
+    pre_multiplied: Vec<Complex<f32>>,
+    overlap: Vec<f32>,
+}
+
+/// Uniformly partitioned convolution of one input with several responses, one output each.
+///
+/// The spectrum of every input block is computed once and kept in a single history shared by
+/// all outputs, so adding an output only costs its multiply-accumulate and inverse FFT. Updates
+/// are linked like in the `MultiChannelConvolver`: they apply to the updated outputs at the same
+/// sample and drop the tail of their previous responses.
+#[derive(Clone)]
+pub struct OneToManyConvolver {
+    max_response_length: usize,
+    block_size: usize,
+    seg_count: usize,
+    responses: Vec<ResponseSpectra>,
+    outputs: Vec<OutputState>,
+    segments: Vec<Vec<Complex<f32>>>,
+    input_buffer: Vec<f32>,
+    fft: Fft,
+    fft_buffer: Vec<f32>,
+    conv: Vec<Complex<f32>>,
+    current: usize,
+    input_buffer_fill: usize,
+    tail_flush: TailFlush,
+}
+
+impl OneToManyConvolver {
+    /// Creates a convolver with one output per response.
+    pub fn new(responses: &[&[Sample]], block_size: usize, max_response_length: usize) -> Self {
+        let block_size = block_size.next_power_of_two();
+        let seg_size = 2 * block_size;
+        let seg_count = (max_response_length as f64 / block_size as f64).ceil() as usize;
+        let fft_complex_size = complex_size(seg_size);
+
+        let mut fft = Fft::default();
+        fft.init(seg_size);
+
+        let output = OutputState {
+            pre_multiplied: vec![Complex::new(0., 0.); fft_complex_size],
+            overlap: vec![0.; block_size],
+        };
+
+        let mut convolver = Self {
+            max_response_length,
+            block_size,
+            seg_count,
+            responses: vec![ResponseSpectra::new(seg_count, fft_complex_size); responses.len()],
+            outputs: vec![output; responses.len()],
+            segments: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count],
+            input_buffer: vec![0.; block_size],
+            fft,
+            fft_buffer: vec![0.; seg_size],
+            conv: vec![Complex::new(0., 0.); fft_complex_size],
+            current: 0,
+            input_buffer_fill: 0,
+            tail_flush: TailFlush::default(),
+        };
+        convolver.update(responses);
+        convolver
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Replaces the responses of all outputs at once. Real-time safe.
+    pub fn update(&mut self, responses: &[&[Sample]]) {
+        assert_eq!(
+            responses.len(),
+            self.responses.len(),
+            "one response per output"
+        );
+        for (index, response) in responses.iter().enumerate() {
+            self.update_output(index, response);
+        }
+    }
+
+    /// Replaces the response of a single output, the other outputs are not affected.
+    /// Real-time safe.
+    pub fn update_output(&mut self, index: usize, response: &[Sample]) {
+        if response.len() > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+        self.responses[index].prepare(response, self.block_size, &self.fft, &mut self.fft_buffer);
+
+        // the previous response stops contributing, the input history is kept
+        let output = &mut self.outputs[index];
+        output.pre_multiplied.fill(Complex::new(0., 0.));
+        output.overlap.fill(0.);
+    }
+
+    /// Convolves `input` with every response, one output slice per response of the same
+    /// length as `input`.
+    pub fn process<O: AsMut<[Sample]>>(&mut self, input: &[Sample], output: &mut [O]) {
+        assert_eq!(output.len(), self.outputs.len(), "one output per response");
+        assert!(
+            output
+                .iter_mut()
+                .all(|output| output.as_mut().len() == input.len()),
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        if self.seg_count == 0 {
+            for output in output.iter_mut() {
+                output.as_mut().fill(0.);
+            }
+            return;
+        }
+
+        let mut processed = 0;
+        while processed < input.len() {
+            let processing = std::cmp::min(
+                input.len() - processed,
+                self.block_size - self.input_buffer_fill,
+            );
+            let chunk = processed..processed + processing;
+            self.convolve_chunk(&input[chunk.clone()], output, chunk.start);
+            processed += processing;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        0
+    }
+
+    pub fn response_len(&self, output: usize) -> usize {
+        self.responses[output].response_len
+    }
+
+    /// Number of samples any output may still be non-zero after the input went silent.
+    pub fn tail_samples(&self) -> usize {
+        self.responses
+            .iter()
+            .map(|response| response.response_len.saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Clears all processing history. Real-time safe.
+    pub fn reset(&mut self) {
+        for segment in &mut self.segments {
+            segment.fill(Complex::new(0., 0.));
+        }
+        for output in &mut self.outputs {
+            output.pre_multiplied.fill(Complex::new(0., 0.));
+            output.overlap.fill(0.);
+        }
+        self.input_buffer.fill(0.);
+        self.current = 0;
+        self.input_buffer_fill = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of every output like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        assert_eq!(output.len(), self.outputs.len(), "one output per response");
+        let len = output.first_mut().map_or(0, |output| output.as_mut().len());
+        assert!(
+            output.iter_mut().all(|output| output.as_mut().len() == len),
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let mut processed = 0;
+        while processed < written {
+            let processing = std::cmp::min(
+                std::cmp::min(written - processed, SILENCE.len()),
+                self.block_size - self.input_buffer_fill,
+            );
+            self.convolve_chunk(&SILENCE[..processing], output, processed);
+            processed += processing;
+        }
+        for output in output.iter_mut() {
+            output.as_mut()[written..].fill(0.);
+        }
+        self.tail_flush.advance(written);
+        written
+    }
+
+    // Convolves a chunk that does not reach beyond the current block into all outputs,
+    // starting at `offset` of the output slices
+    fn convolve_chunk<O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[Sample],
+        output: &mut [O],
+        offset: usize,
+    ) {
+        let block_size = self.block_size;
+        let input_buffer_pos = self.input_buffer_fill;
+        let processing = input.len();
+
+        self.input_buffer[input_buffer_pos..input_buffer_pos + processing].copy_from_slice(input);
+
+        // Forward FFT, once for all outputs
+        copy_and_pad(&mut self.fft_buffer, &self.input_buffer, block_size);
+        self.fft
+            .forward(&mut self.fft_buffer, &mut self.segments[self.current])
+            .unwrap();
+
+        for ((response, state), output) in self
+            .responses
+            .iter()
+            .zip(&mut self.outputs)
+            .zip(output.iter_mut())
+        {
+            // complex multiplication
+            if input_buffer_pos == 0 {
+                state.pre_multiplied.fill(Complex::new(0., 0.));
+                response.multiply_history(&mut state.pre_multiplied, &self.segments, self.current);
+            }
+            self.conv.copy_from_slice(&state.pre_multiplied);
+            response.multiply_latest(&mut self.conv, &self.segments[self.current]);
+
+            // Backward FFT
+            self.fft
+                .inverse(&mut self.conv, &mut self.fft_buffer)
+                .unwrap();
+
+            // Add overlap
+            sum(
+                &mut output.as_mut()[offset..offset + processing],
+                &self.fft_buffer[input_buffer_pos..input_buffer_pos + processing],
+                &state.overlap[input_buffer_pos..input_buffer_pos + processing],
+            );
+
+            // Save the overlap once the block is complete
+            if input_buffer_pos + processing == block_size {
+                state
+                    .overlap
+                    .copy_from_slice(&self.fft_buffer[block_size..2 * block_size]);
+            }
+        }
+
+        // Move on to the next segment once the block is complete
+        self.input_buffer_fill += processing;
+        if self.input_buffer_fill == block_size {
+            self.input_buffer.fill(0.);
+            self.input_buffer_fill = 0;
+            self.current = if self.current > 0 {
+                self.current - 1
+            } else {
+                self.seg_count - 1
+            };
+        }
+    }
+}
+
+impl PlanarConvolution for OneToManyConvolver {
+    fn input_count(&self) -> usize {
+        1
+    }
+
+    fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]) {
+        assert_eq!(input.len(), 1, "one input slice");
+        OneToManyConvolver::process(self, input[0], output);
+    }
+
+    fn tail_samples(&self) -> usize {
+        OneToManyConvolver::tail_samples(self)
+    }
+
+    fn reset(&mut self) {
+        OneToManyConvolver::reset(self);
+    }
+
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize {
+        OneToManyConvolver::flush(self, output)
+    }
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,271 @@
+use rustfft::num_complex::Complex;
+
+use crate::config::{ConfigError, ConvolverConfig, FftBackend};
+use crate::fft_convolver::LatencyMode;
+use crate::fft_convolver::{
+    complex_multiply_accumulate, complex_size, copy_and_pad, response_from_partitions, Fft,
+};
+use crate::{flush_tail, Convolution, Sample, TailFlush};
+
+/// Uniformly partitioned convolution using overlap-save.
+///
+/// Instead of zero-padding every input block and adding up overlapping output halves like the
+/// `FFTConvolver`, each transform covers a sliding window of the last two input blocks and the
+/// aliased first half of the result is discarded. This removes the overlap buffer and its
+/// summation pass.
+///
+/// `update` swaps the response for the whole input history, like the `DirectConvolver` does,
+/// instead of dropping the tail of the previous response.
+#[derive(Default, Clone)]
+pub struct OverlapSaveConvolver {
+    max_response_length: usize,
+    response_len: usize,
+    block_size: usize,
+    seg_count: usize,
+    active_seg_count: usize,
+    segments: Vec<Vec<Complex<f32>>>,
+    segments_ir: Vec<Vec<Complex<f32>>>,
+    fft_buffer: Vec<f32>,
+    fft: Fft,
+    pre_multiplied: Vec<Complex<f32>>,
+    pre_multiplied_stale: bool,
+    conv: Vec<Complex<f32>>,
+    current: usize,
+    // previous input block followed by the current one
+    window: Vec<f32>,
+    window_fill: usize,
+    tail_flush: TailFlush,
+}
+
+impl Convolution for OverlapSaveConvolver {
+    fn init(response: &[Sample], block_size: usize, max_response_length: usize) -> Self {
+        if max_response_length < response.len() {
+            panic!(
+                "max_response_length must be at least the length of the initial impulse response"
+            );
+        }
+
+        let block_size = block_size.next_power_of_two();
+        let seg_size = 2 * block_size;
+        let seg_count = (max_response_length as f64 / block_size as f64).ceil() as usize;
+        let fft_complex_size = complex_size(seg_size);
+
+        let mut fft = Fft::default();
+        fft.init(seg_size);
+
+        let mut convolver = Self {
+            max_response_length,
+            response_len: 0,
+            block_size,
+            seg_count,
+            active_seg_count: 0,
+            segments: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count],
+            segments_ir: vec![vec![Complex::new(0., 0.); fft_complex_size]; seg_count],
+            fft_buffer: vec![0.; seg_size],
+            fft,
+            pre_multiplied: vec![Complex::new(0., 0.); fft_complex_size],
+            pre_multiplied_stale: false,
+            conv: vec![Complex::new(0., 0.); fft_complex_size],
+            current: 0,
+            window: vec![0.; seg_size],
+            window_fill: 0,
+            tail_flush: TailFlush::default(),
+        };
+        convolver.update(response);
+        convolver
+    }
+
+    fn from_config(response: &[Sample], config: &ConvolverConfig) -> Result<Self, ConfigError> {
+        if config.latency_mode() != LatencyMode::Zero {
+            return Err(ConfigError::UnsupportedLatencyMode(config.latency_mode()));
+        }
+        let mut convolver = Self::init(response, config.block_size(), config.max_response_length());
+        convolver.set_fft_backend(config.fft_backend());
+        Ok(convolver)
+    }
+
+    fn update(&mut self, response: &[Sample]) {
+        let response_len = response.len();
+        if response_len > self.max_response_length {
+            panic!("New impulse response is longer than max response length");
+        }
+
+        if self.max_response_length == 0 {
+            return;
+        }
+
+        self.response_len = response_len;
+        self.active_seg_count = (response_len as f64 / self.block_size as f64).ceil() as usize;
+
+        for (i, segment) in self.segments_ir.iter_mut().enumerate() {
+            if i < self.active_seg_count {
+                let offset = i * self.block_size;
+                let size_copy = std::cmp::min(self.block_size, response_len - offset);
+                copy_and_pad(&mut self.fft_buffer, &response[offset..], size_copy);
+                self.fft.forward(&mut self.fft_buffer, segment).unwrap();
+            } else {
+                segment.fill(Complex::new(0., 0.));
+            }
+        }
+
+        // the partitions of the previous response must not be used for the rest of the block
+        self.pre_multiplied_stale = true;
+    }
+
+    fn process(&mut self, input: &[Sample], output: &mut [Sample]) {
+        self.tail_flush.restart();
+        if self.active_seg_count == 0 {
+            // keep the window up to date for a later update
+            if self.max_response_length > 0 {
+                self.push_input(input);
+            }
+            output.fill(0.);
+            return;
+        }
+
+        let block_size = self.block_size;
+        let mut processed = 0;
+        while processed < output.len() {
+            let window_was_new = self.window_fill == 0;
+            let processing = std::cmp::min(output.len() - processed, block_size - self.window_fill);
+
+            let window_pos = block_size + self.window_fill;
+            self.window[window_pos..window_pos + processing]
+                .copy_from_slice(&input[processed..processed + processing]);
+
+            // Forward FFT of the whole window, the not yet received samples are zero
+            self.fft_buffer.copy_from_slice(&self.window);
+            if let Err(_err) = self
+                .fft
+                .forward(&mut self.fft_buffer, &mut self.segments[self.current])
+            {
+                output.fill(0.);
+                return; // error!
+            }
+
+            // complex multiplication
+            if window_was_new || self.pre_multiplied_stale {
+                self.pre_multiplied.fill(Complex::new(0., 0.));
+                for i in 1..self.active_seg_count {
+                    let index_audio = (self.current + i) % self.seg_count;
+                    complex_multiply_accumulate(
+                        &mut self.pre_multiplied,
+                        &self.segments_ir[i],
+                        &self.segments[index_audio],
+                    );
+                }
+                self.pre_multiplied_stale = false;
+            }
+            self.conv.clone_from_slice(&self.pre_multiplied);
+            complex_multiply_accumulate(
+                &mut self.conv,
+                &self.segments[self.current],
+                &self.segments_ir[0],
+            );
+
+            // Backward FFT
+            if let Err(_err) = self.fft.inverse(&mut self.conv, &mut self.fft_buffer) {
+                output.fill(0.);
+                return; // error!
+            }
+
+            // Save: only the second half is free of circular aliasing
+            output[processed..processed + processing]
+                .copy_from_slice(&self.fft_buffer[window_pos..window_pos + processing]);
+
+            self.window_fill += processing;
+            if self.window_fill == block_size {
+                self.advance_window();
+            }
+            processed += processing;
+        }
+    }
+
+    fn response_len(&self) -> usize {
+        self.response_len
+    }
+
+    fn reset(&mut self) {
+        for segment in &mut self.segments {
+            segment.fill(Complex::new(0., 0.));
+        }
+        self.pre_multiplied.fill(Complex::new(0., 0.));
+        self.conv.fill(Complex::new(0., 0.));
+        self.window.fill(0.);
+        self.window_fill = 0;
+        self.current = 0;
+        self.tail_flush = TailFlush::default();
+    }
+
+    fn flush(&mut self, output: &mut [Sample]) -> usize {
+        let (tail_flush, written) = flush_tail(self, self.tail_flush, output);
+        self.tail_flush = tail_flush;
+        written
+    }
+
+    fn reconfigure(&mut self, max_block_size: usize, max_response_length: usize) {
+        let response = response_from_partitions(
+            &self.fft,
+            &self.segments_ir,
+            self.block_size,
+            self.response_len,
+        );
+        let fft_backend = self.fft.backend();
+        *self = Self::init(&response, max_block_size, max_response_length);
+        self.set_fft_backend(fft_backend);
+    }
+}
+
+impl OverlapSaveConvolver {
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Sets the FFT implementation, see `FFTConvolver::set_fft_backend`.
+    pub fn set_fft_backend(&mut self, backend: FftBackend) {
+        if backend != self.fft.backend() {
+            self.fft = Fft::with_backend(backend);
+            self.fft.init(2 * self.block_size);
+        }
+    }
+
+    pub fn fft_backend(&self) -> FftBackend {
+        self.fft.backend()
+    }
+
+    // Slides the window by one block and moves on to the next segment
+    fn advance_window(&mut self) {
+        let block_size = self.block_size;
+        self.window.copy_within(block_size.., 0);
+        self.window[block_size..].fill(0.);
+        self.window_fill = 0;
+
+        self.current = if self.current > 0 {
+            self.current - 1
+        } else {
+            self.seg_count - 1
+        };
+    }
+
+    // Transforms the input without convolving it, so the history is complete once a
+    // response is set
+    fn push_input(&mut self, input: &[Sample]) {
+        let block_size = self.block_size;
+        let mut processed = 0;
+        while processed < input.len() {
+            let processing = std::cmp::min(input.len() - processed, block_size - self.window_fill);
+            let window_pos = block_size + self.window_fill;
+            self.window[window_pos..window_pos + processing]
+                .copy_from_slice(&input[processed..processed + processing]);
+            self.window_fill += processing;
+            if self.window_fill == block_size {
+                self.fft_buffer.copy_from_slice(&self.window);
+                self.fft
+                    .forward(&mut self.fft_buffer, &mut self.segments[self.current])
+                    .unwrap();
+                self.advance_window();
+            }
+            processed += processing;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::Sample;
+
+/// Sample formats that can be converted to and from the `Sample` type of the convolvers.
+///
+/// Integer formats are scaled to the range -1 to 1. Converting back clips samples outside of
+/// that range to the limits of the format.
+pub trait SampleFormat: Copy {
+    fn to_sample(self) -> Sample;
+    fn from_sample(sample: Sample) -> Self;
+}
+
+impl SampleFormat for f32 {
+    fn to_sample(self) -> Sample {
+        self
+    }
+
+    fn from_sample(sample: Sample) -> Self {
+        sample
+    }
+}
+
+impl SampleFormat for i16 {
+    fn to_sample(self) -> Sample {
+        self as Sample / 32768.0
+    }
+
+    fn from_sample(sample: Sample) -> Self {
+        (sample * 32768.0)
+            .round()
+            .clamp(i16::MIN as Sample, i16::MAX as Sample) as i16
+    }
+}
+
+impl SampleFormat for i32 {
+    fn to_sample(self) -> Sample {
+        (self as f64 / 2147483648.0) as Sample
+    }
+
+    fn from_sample(sample: Sample) -> Self {
+        (sample as f64 * 2147483648.0)
+            .round()
+            .clamp(i32::MIN as f64, i32::MAX as f64) as i32
+    }
+}
+
+/// Packed 24-bit sample in little-endian byte order, as found in files and driver buffers.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct I24(pub [u8; 3]);
+
+impl I24 {
+    pub const MIN: i32 = -(1 << 23);
+    pub const MAX: i32 = (1 << 23) - 1;
+
+    /// Keeps the lower 24 bits of `value`.
+    pub fn from_i32(value: i32) -> Self {
+        let bytes = value.to_le_bytes();
+        Self([bytes[0], bytes[1], bytes[2]])
+    }
+
+    pub fn to_i32(self) -> i32 {
+        // sign extension by shifting the sign bit into place and back
+        i32::from_le_bytes([0, self.0[0], self.0[1], self.0[2]]) >> 8
+    }
+}
+
+impl SampleFormat for I24 {
+    fn to_sample(self) -> Sample {
+        self.to_i32() as Sample / 8388608.0
+    }
+
+    fn from_sample(sample: Sample) -> Self {
+        let value = (sample * 8388608.0)
+            .round()
+            .clamp(Self::MIN as Sample, Self::MAX as Sample);
+        Self::from_i32(value as i32)
+    }
+}
+
+#[test]
+fn test_sample_format_conversion() {
+    for value in [I24::MIN, -1234567, -1, 0, 1, 7654321, I24::MAX] {
+        assert_eq!(I24::from_i32(value).to_i32(), value);
+        assert_eq!(
+            I24::from_sample(I24::from_i32(value).to_sample()).to_i32(),
+            value
+        );
+    }
+    for value in [i16::MIN, -1234, 0, 4321, i16::MAX] {
+        assert_eq!(i16::from_sample(value.to_sample()), value);
+    }
+    assert_eq!(i32::from_sample(-1.0), i32::MIN);
+    assert_eq!(i32::from_sample(0.25), 1 << 29);
+
+    // clipping
+    assert_eq!(i16::from_sample(1.5), i16::MAX);
+    assert_eq!(i16::from_sample(-1.5), i16::MIN);
+    assert_eq!(I24::from_sample(2.0).to_i32(), I24::MAX);
+    assert_eq!(i32::from_sample(1.0), i32::MAX);
+}
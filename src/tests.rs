@@ -1,9 +1,24 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::crossfade_convolver::CrossfadeConvolver;
-    use crate::fft_convolver::FFTConvolver;
+    use crate::auto_convolver::{AutoConvolver, Engine};
+    use crate::batched_convolver::BatchedConvolver;
+    use crate::bypass_convolver::BypassConvolver;
+    use crate::config::{ConfigError, ConvolverConfig, FftBackend};
+    use crate::convolution_reverb::ConvolutionReverb;
+    use crate::crossfade_convolver::{CrossfadeConvolver, EitherConvolver};
+    use crate::direct_convolver::DirectConvolver;
+    use crate::fft_convolver::{FFTConvolver, LatencyMode, TwoStageFFTConvolver, UpdateMode};
+    use crate::interleaved_convolver::InterleavedConvolver;
+    use crate::matrix_convolver::MatrixConvolver;
+    use crate::multi_channel_convolver::MultiChannelConvolver;
+    use crate::one_to_many_convolver::OneToManyConvolver;
+    use crate::overlap_save_convolver::OverlapSaveConvolver;
+    use crate::sample_format::{SampleFormat, I24};
+    use crate::true_stereo_convolver::TrueStereoConvolver;
     use crate::{Convolution, Sample};
 
+    #[allow(clippy::needless_range_loop)]
     fn generate_sinusoid(
         length: usize,
         frequency: f32,
@@ -18,6 +33,16 @@ mod tests {
         signal
     }
 
+    fn convolve_direct(input: &[Sample], response: &[Sample]) -> Vec<Sample> {
+        (0..input.len())
+            .map(|n| {
+                (0..response.len().min(n + 1))
+                    .map(|k| input[n - k] * response[k])
+                    .sum()
+            })
+            .collect()
+    }
+
     #[test]
     fn fft_convolver_update_is_reset() {
         let block_size = 512;
@@ -118,4 +143,1663 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn two_stage_fft_convolver_update_matches_init() {
+        let response_a = generate_sinusoid(1500, 400.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(3000, 700.0, 48000.0, 0.3);
+        let max_response_length = response_b.len();
+        let mut convolver = TwoStageFFTConvolver::init(&response_a, 128, max_response_length);
+        convolver.update(&response_b);
+
+        let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(256).zip(output.chunks_mut(256)) {
+            convolver.process(input, output);
+        }
+
+        let expected = convolve_direct(&input, &response_b);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn crossfade_between_convolver_types() {
+        type Engine = EitherConvolver<FFTConvolver, TwoStageFFTConvolver>;
+
+        let block_size = 256;
+        let crossfade_samples = 512;
+        let room = generate_sinusoid(block_size, 1000.0, 48000.0, 1.0);
+        let hall = generate_sinusoid(4000, 500.0, 48000.0, 0.2);
+
+        let mut crossfade_convolver = CrossfadeConvolver::new(
+            Engine::init(&room, block_size, room.len()),
+            hall.len(),
+            block_size,
+            crossfade_samples,
+        );
+        let mut reference = TwoStageFFTConvolver::init(&hall, block_size, hall.len());
+
+        let num_input_blocks = 32;
+        let input = generate_sinusoid(num_input_blocks * block_size, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; block_size];
+        let mut output_reference = vec![0.0; block_size];
+
+        let update_index = 8;
+        for (i, block) in input.chunks(block_size).enumerate() {
+            if i == update_index {
+                let hall_convolver = Engine::Right(reference.clone());
+                assert!(crossfade_convolver.replace(hall_convolver).is_ok());
+                assert!(crossfade_convolver.is_crossfading());
+                let spare = Engine::init(&room, block_size, room.len());
+                assert!(crossfade_convolver.replace(spare).is_err());
+            }
+
+            crossfade_convolver.process(block, &mut output);
+            if i >= update_index {
+                reference.process(block, &mut output_reference);
+            }
+
+            // hold (block size) plus fade
+            if i >= update_index + 3 {
+                for (lhs, rhs) in output.iter().zip(&output_reference) {
+                    assert!((lhs - rhs).abs() < 1e-6);
+                }
+            }
+        }
+        assert!(!crossfade_convolver.is_crossfading());
+    }
+
+    #[test]
+    fn crossfade_convolver_arbitrary_buffer_lengths() {
+        let max_buffer_size = 256;
+        let response = generate_sinusoid(1024, 1000.0, 48000.0, 0.5);
+        let mut convolver = FFTConvolver::init(&response, max_buffer_size, response.len());
+        let mut crossfade_convolver =
+            CrossfadeConvolver::new(convolver.clone(), response.len(), max_buffer_size, 512);
+
+        let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        let mut output_crossfade_convolver = vec![0.0; input.len()];
+        convolver.process(&input, &mut output);
+
+        // host buffer sizes both below and above max_buffer_size
+        let mut processed = 0;
+        for buffer_size in [100, 1000, 256, 7, 2000].into_iter().cycle() {
+            let end = (processed + buffer_size).min(input.len());
+            crossfade_convolver.process(
+                &input[processed..end],
+                &mut output_crossfade_convolver[processed..end],
+            );
+            processed = end;
+            if processed == input.len() {
+                break;
+            }
+        }
+
+        for (lhs, rhs) in output.iter().zip(&output_crossfade_convolver) {
+            assert!((lhs - rhs).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "input and output must have the same length")]
+    fn crossfade_convolver_rejects_mismatched_lengths() {
+        let response = [1.0; 16];
+        let mut convolver = CrossfadeConvolver::new(
+            FFTConvolver::init(&response, 16, response.len()),
+            response.len(),
+            16,
+            16,
+        );
+        convolver.process(&[0.0; 16], &mut [0.0; 8]);
+    }
+
+    #[test]
+    fn crossfade_convolver_reconfigure_with_pending_response() {
+        let block_size = 64;
+        let response_a = generate_sinusoid(300, 1000.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(300, 2000.0, 48000.0, 0.3);
+        let input = generate_sinusoid(2048, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+
+        for pending_len in [400, 800] {
+            let response_c = generate_sinusoid(pending_len, 700.0, 48000.0, 0.4);
+            let mut convolver = CrossfadeConvolver::new(
+                FFTConvolver::init(&response_a, block_size, 1024),
+                1024,
+                block_size,
+                block_size,
+            );
+            convolver.update(&response_b);
+            convolver.update(&response_c);
+            convolver.reconfigure(block_size, 512);
+
+            // a pending response that still fits is faded in after the reconfigure, a longer
+            // one is dropped
+            let expected = if pending_len <= 512 {
+                convolve_direct(&input, &response_c)
+            } else {
+                convolve_direct(&input, &response_b)
+            };
+            convolver.process(&input, &mut output);
+            for (lhs, rhs) in output[2 * block_size..]
+                .iter()
+                .zip(&expected[2 * block_size..])
+            {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "input and output must have the same length")]
+    fn direct_convolver_rejects_mismatched_lengths() {
+        let response = [1.0; 16];
+        let mut convolver = DirectConvolver::init(&response, 16, response.len());
+        convolver.process(&[0.0; 8], &mut [0.0; 16]);
+    }
+
+    #[test]
+    fn bypass_convolver_fades_to_dry_and_back() {
+        let block_size = 256;
+        let crossfade_samples = 128;
+        let response = generate_sinusoid(1024, 1000.0, 48000.0, 0.5);
+        let mut convolver = FFTConvolver::init(&response, block_size, response.len());
+        let mut bypass_convolver =
+            BypassConvolver::new(convolver.clone(), block_size, crossfade_samples);
+
+        let input = generate_sinusoid(8 * block_size, 1300.0, 48000.0, 1.0);
+        let mut output_wet = vec![0.0; block_size];
+        let mut output = vec![0.0; block_size];
+
+        for (i, block) in input.chunks(block_size).enumerate() {
+            match i {
+                2 => bypass_convolver.set_bypass(true),
+                5 => bypass_convolver.set_bypass(false),
+                _ => (),
+            }
+
+            convolver.process(block, &mut output_wet);
+            bypass_convolver.process(block, &mut output);
+
+            let expected = match i {
+                2..=4 => block,
+                _ => &output_wet[..],
+            };
+            let faded = if i == 2 || i == 5 {
+                crossfade_samples
+            } else {
+                0
+            };
+            for j in faded..block_size {
+                assert!((output[j] - expected[j]).abs() < 1e-4);
+            }
+            for j in 0..faded {
+                let (from, to) = if i == 2 {
+                    (output_wet[j], block[j])
+                } else {
+                    (block[j], output_wet[j])
+                };
+                assert!(output[j] >= from.min(to) - 1e-4 && output[j] <= from.max(to) + 1e-4);
+            }
+        }
+        assert!(!bypass_convolver.is_bypassed());
+    }
+
+    #[test]
+    fn bypass_convolver_toggled_twice_within_a_block() {
+        let block_size = 256;
+        let crossfade_samples = 128;
+        let response = generate_sinusoid(1024, 1000.0, 48000.0, 0.5);
+        let mut convolver = FFTConvolver::init(&response, block_size, response.len());
+        let mut bypass_convolver =
+            BypassConvolver::new(convolver.clone(), block_size, crossfade_samples);
+
+        let input = generate_sinusoid(4 * block_size, 1300.0, 48000.0, 1.0);
+        let mut output_wet = vec![0.0; block_size];
+        let mut output = vec![0.0; block_size];
+
+        for (i, block) in input.chunks(block_size).enumerate() {
+            // the second toggle cancels the fade before it produced a single sample
+            if i == 1 {
+                bypass_convolver.set_bypass(true);
+                bypass_convolver.set_bypass(false);
+                assert!(!bypass_convolver.is_crossfading());
+            }
+            // a later fade still reaches the dry input
+            if i == 2 {
+                bypass_convolver.set_bypass(true);
+            }
+
+            convolver.process(block, &mut output_wet);
+            bypass_convolver.process(block, &mut output);
+
+            let expected = if i >= 2 { block } else { &output_wet[..] };
+            let faded = if i == 2 { crossfade_samples } else { 0 };
+            for j in faded..block_size {
+                assert!((output[j] - expected[j]).abs() < 1e-4);
+            }
+        }
+        assert!(bypass_convolver.is_bypassed());
+        assert!(!bypass_convolver.is_crossfading());
+    }
+
+    #[test]
+    fn bypass_convolver_follows_latency_changes() {
+        let block_size = 64;
+        let response = generate_sinusoid(300, 1000.0, 48000.0, 0.5);
+        let mut bypass_convolver = BypassConvolver::new(
+            FFTConvolver::init(&response, block_size, response.len()),
+            block_size,
+            16,
+        );
+        bypass_convolver.set_bypass(true);
+
+        let input = generate_sinusoid(4 * block_size, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        bypass_convolver.process(&input, &mut output);
+        assert_eq!(output[100..], input[100..]);
+
+        // the dry input is delayed like the wet output once the latency grows
+        bypass_convolver
+            .convolver_mut()
+            .set_latency_mode(LatencyMode::Block);
+        assert_eq!(bypass_convolver.latency(), block_size);
+        bypass_convolver.process(&input, &mut output);
+        assert!(output[..block_size].iter().all(|sample| *sample == 0.0));
+        assert_eq!(output[block_size..], input[..input.len() - block_size]);
+
+        bypass_convolver
+            .convolver_mut()
+            .set_latency_mode(LatencyMode::Zero);
+        bypass_convolver.reset();
+        bypass_convolver.set_bypass(true);
+        bypass_convolver.process(&input, &mut output);
+        assert_eq!(output[16..], input[16..]);
+    }
+
+    #[test]
+    fn fft_convolver_ramped_update() {
+        let block_size = 128;
+        let ramp_samples = 200;
+        let response_a = generate_sinusoid(1024, 1000.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(1024, 2000.0, 48000.0, 0.3);
+        let mut convolver = FFTConvolver::init(&response_a, block_size, response_a.len());
+        let mut convolver_ramp = convolver.clone();
+        convolver_ramp.set_update_mode(UpdateMode::Ramp(ramp_samples));
+
+        let input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        let mut output_ramp = vec![0.0; input.len()];
+
+        // the ramped convolver switches once it is fully ducked
+        let update_at = 1000;
+        let switch_at = update_at + ramp_samples;
+        convolver.process(&input[..switch_at], &mut output[..switch_at]);
+        convolver.update(&response_b);
+        convolver.process(&input[switch_at..], &mut output[switch_at..]);
+
+        convolver_ramp.process(&input[..update_at], &mut output_ramp[..update_at]);
+        convolver_ramp.update(&response_b);
+        for (input, output) in input[update_at..]
+            .chunks(100)
+            .zip(output_ramp[update_at..].chunks_mut(100))
+        {
+            convolver_ramp.process(input, output);
+        }
+
+        for i in 0..input.len() {
+            let gain = if i < update_at {
+                1.0
+            } else if i < switch_at {
+                (switch_at - i - 1) as Sample / ramp_samples as Sample
+            } else if i < switch_at + ramp_samples {
+                (i - switch_at + 1) as Sample / ramp_samples as Sample
+            } else {
+                1.0
+            };
+            assert!((output[i] * gain - output_ramp[i]).abs() < 1e-4);
+        }
+        assert_eq!(output_ramp[switch_at - 1], 0.0);
+    }
+
+    #[test]
+    fn fft_convolver_update_preserving_history() {
+        let block_size = 64;
+        let response_a = generate_sinusoid(700, 1000.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(300, 2000.0, 48000.0, 0.3);
+        let response_c = generate_sinusoid(1000, 700.0, 48000.0, 0.2);
+        let input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+
+        // to a shorter and to a longer response, on a block boundary and within a block
+        for (response_old, response_new) in [(&response_a, &response_b), (&response_b, &response_c)]
+        {
+            for update_at in [1024, 1100] {
+                let mut convolver = FFTConvolver::init(response_old, block_size, 1000);
+                convolver.set_update_mode(UpdateMode::PreserveHistory);
+
+                let mut output = vec![0.0; input.len()];
+                convolver.process(&input[..update_at], &mut output[..update_at]);
+                convolver.update(response_new);
+                for (input, output) in input[update_at..]
+                    .chunks(50)
+                    .zip(output[update_at..].chunks_mut(50))
+                {
+                    convolver.process(input, output);
+                }
+
+                let mut input_before = input.clone();
+                input_before[update_at..].fill(0.0);
+                let mut input_after = input.clone();
+                input_after[..update_at].fill(0.0);
+                let expected_before = convolve_direct(&input_before, response_old);
+                let expected_after = convolve_direct(&input_after, response_new);
+
+                for i in 0..input.len() {
+                    let expected = expected_before[i] + expected_after[i];
+                    assert!((output[i] - expected).abs() < 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fft_convolver_skips_silence() {
+        let block_size = 64;
+        let response_a = generate_sinusoid(700, 1000.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(300, 2000.0, 48000.0, 0.3);
+        let mut input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+        input[500..2500].fill(0.0);
+        input[2600..].fill(0.0);
+        let last_sound = |end: usize| input[..end].iter().rposition(|sample| *sample != 0.0);
+
+        // the update happens while the previous response still rings out
+        let update_at = 1100;
+        for preserve_history in [false, true] {
+            let mut convolver = FFTConvolver::init(&response_a, block_size, response_a.len());
+            assert!(convolver.is_idle());
+            if preserve_history {
+                convolver.set_update_mode(UpdateMode::PreserveHistory);
+            }
+
+            let mut output = vec![0.0; input.len()];
+            let mut processed = 0;
+            for chunk_size in [50, 64, 7, 130].into_iter().cycle() {
+                let end = (processed + chunk_size).min(input.len());
+                if preserve_history && (processed..end).contains(&update_at) {
+                    convolver.process(
+                        &input[processed..update_at],
+                        &mut output[processed..update_at],
+                    );
+                    convolver.update(&response_b);
+                    convolver.process(&input[update_at..end], &mut output[update_at..end]);
+                } else {
+                    convolver.process(&input[processed..end], &mut output[processed..end]);
+                }
+                processed = end;
+
+                let response_len = if preserve_history && end >= update_at {
+                    // the previous response counts until it has rung out
+                    match last_sound(update_at) {
+                        Some(last) if end <= last + response_a.len() => response_a.len(),
+                        _ => response_b.len(),
+                    }
+                } else {
+                    response_a.len()
+                };
+                let idle = match last_sound(end) {
+                    Some(last) => end - 1 - last >= response_len,
+                    None => true,
+                };
+                assert_eq!(convolver.is_idle(), idle);
+                if processed == input.len() {
+                    break;
+                }
+            }
+
+            let expected = if preserve_history {
+                let mut input_before = input.clone();
+                input_before[update_at..].fill(0.0);
+                let mut input_after = input.clone();
+                input_after[..update_at].fill(0.0);
+                let expected_before = convolve_direct(&input_before, &response_a);
+                let expected_after = convolve_direct(&input_after, &response_b);
+                expected_before
+                    .iter()
+                    .zip(&expected_after)
+                    .map(|(before, after)| before + after)
+                    .collect()
+            } else {
+                convolve_direct(&input, &response_a)
+            };
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn fft_convolver_preserved_tail_rings_out_into_silence() {
+        let response_a = generate_sinusoid(737, 1000.0, 48000.0, 0.5);
+        let response_b = generate_sinusoid(166, 2000.0, 48000.0, 0.3);
+        let mut input = generate_sinusoid(6000, 1300.0, 48000.0, 1.0);
+        // silent shortly before the update, so the convolver may go idle right after it
+        input[3653..].fill(0.0);
+
+        let mut convolver = FFTConvolver::init(&response_a, 170, 1000);
+        convolver.set_update_mode(UpdateMode::PreserveHistory);
+        let mut output = vec![0.0; input.len()];
+        convolver.process(&input[..3715], &mut output[..3715]);
+        convolver.update(&response_b);
+        for (input, output) in input[3715..].chunks(50).zip(output[3715..].chunks_mut(50)) {
+            convolver.process(input, output);
+        }
+
+        let expected = convolve_direct(&input, &response_a);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+        assert!(convolver.is_idle());
+    }
+
+    #[test]
+    fn fft_convolver_skips_empty_partitions() {
+        let block_size = 64;
+        // pre-delay and a gated region
+        let mut response = generate_sinusoid(2000, 700.0, 48000.0, 0.5);
+        response[..1000].fill(0.0);
+        response[1300..1600].fill(0.0);
+        let input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+
+        let mut convolver = FFTConvolver::init(&response, block_size, response.len());
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(100).zip(output.chunks_mut(100)) {
+            convolver.process(input, output);
+        }
+        let expected = convolve_direct(&input, &response);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+
+        // the empty partitions of the previous response are skipped as well
+        let mut response_b = response.clone();
+        response_b[1000..1300].fill(0.0);
+        response_b[..100].copy_from_slice(&response[1000..1100]);
+        convolver.set_update_mode(UpdateMode::PreserveHistory);
+        convolver.reset();
+        convolver.process(&input[..1030], &mut output[..1030]);
+        convolver.update(&response_b);
+        convolver.process(&input[1030..], &mut output[1030..]);
+
+        let mut input_before = input.clone();
+        input_before[1030..].fill(0.0);
+        let mut input_after = input.clone();
+        input_after[..1030].fill(0.0);
+        let expected_before = convolve_direct(&input_before, &response);
+        let expected_after = convolve_direct(&input_after, &response_b);
+        for i in 0..input.len() {
+            assert!((output[i] - expected_before[i] - expected_after[i]).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn fft_convolver_update_range() {
+        let block_size = 64;
+        let response = generate_sinusoid(1000, 700.0, 48000.0, 0.5);
+        let input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+
+        let mut edited = response.clone();
+        let mut convolver = FFTConvolver::init(&response, block_size, 1500);
+        // within a partition, across partitions, silencing a partition and growing the response
+        let edits = [
+            (10, generate_sinusoid(20, 2000.0, 48000.0, 0.2)),
+            (300, generate_sinusoid(250, 1500.0, 48000.0, 0.4)),
+            (640, vec![0.0; 64]),
+            (1100, generate_sinusoid(300, 900.0, 48000.0, 0.3)),
+        ];
+        for (offset, samples) in &edits {
+            convolver.update_range(*offset, samples);
+            edited.resize(edited.len().max(offset + samples.len()), 0.0);
+            edited[*offset..offset + samples.len()].copy_from_slice(samples);
+        }
+        assert_eq!(convolver.response_len(), 1400);
+
+        // the samples around an edit stay exact, however often it is repeated
+        for _ in 0..100 {
+            convolver.update_range(edits[0].0, &edits[0].1);
+        }
+        assert_eq!(convolver.response(), edited);
+
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(100).zip(output.chunks_mut(100)) {
+            convolver.process(input, output);
+        }
+        let expected = convolve_direct(&input, &edited);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+    }
+
+    fn check_reset_matches_init<T: Convolution>(mut convolver: T, response: &[Sample]) {
+        let mut fresh = convolver.clone();
+        let input = generate_sinusoid(3000, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        let mut output_fresh = vec![0.0; input.len()];
+
+        convolver.process(&input[..1234], &mut output[..1234]);
+        convolver.reset();
+        convolver.process(&input, &mut output);
+        fresh.process(&input, &mut output_fresh);
+
+        assert_eq!(convolver.response_len(), response.len());
+        assert_eq!(convolver.latency(), 0);
+        assert_eq!(convolver.tail_samples(), response.len() - 1);
+        for (lhs, rhs) in output.iter().zip(&output_fresh) {
+            assert!((lhs - rhs).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn reset_matches_init() {
+        let response = generate_sinusoid(2500, 700.0, 48000.0, 0.3);
+        let max_response_length = 4096;
+        check_reset_matches_init(
+            FFTConvolver::init(&response, 256, max_response_length),
+            &response,
+        );
+        check_reset_matches_init(
+            TwoStageFFTConvolver::init(&response, 256, max_response_length),
+            &response,
+        );
+
+        let mut crossfade_convolver = CrossfadeConvolver::new(
+            FFTConvolver::init(&response[..100], 256, max_response_length),
+            max_response_length,
+            256,
+            1024,
+        );
+        crossfade_convolver.update(&response);
+        assert!(crossfade_convolver.is_crossfading());
+        // a response that is still pending is dropped as well
+        crossfade_convolver.update(&response[..100]);
+        crossfade_convolver.reset();
+        assert!(!crossfade_convolver.is_crossfading());
+        check_reset_matches_init(crossfade_convolver, &response);
+    }
+
+    fn check_flush_matches_silence<T: Convolution>(mut convolver: T) {
+        let input = generate_sinusoid(1500, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        convolver.process(&input, &mut output);
+
+        let tail_samples = convolver.tail_samples();
+        let mut reference = convolver.clone();
+        let mut expected = vec![0.0; tail_samples];
+        reference.process(&vec![0.0; tail_samples], &mut expected);
+
+        // odd buffer sizes, the last one extends past the end of the tail
+        let mut flushed = vec![1.0; tail_samples + 1000];
+        let mut written = 0;
+        for chunk in flushed.chunks_mut(333) {
+            written += convolver.flush(chunk);
+        }
+        assert_eq!(written, tail_samples);
+        for (lhs, rhs) in flushed.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-4);
+        }
+        assert!(flushed[tail_samples..].iter().all(|sample| *sample == 0.0));
+        assert_eq!(convolver.flush(&mut output), 0);
+
+        convolver.process(&input, &mut output);
+        convolver.reset();
+        assert_eq!(convolver.flush(&mut output), 0);
+    }
+
+    #[test]
+    fn flush_drains_tail() {
+        let response = generate_sinusoid(2500, 700.0, 48000.0, 0.3);
+        let max_response_length = 4096;
+        check_flush_matches_silence(FFTConvolver::init(&response, 256, max_response_length));
+        check_flush_matches_silence(TwoStageFFTConvolver::init(
+            &response,
+            256,
+            max_response_length,
+        ));
+        check_flush_matches_silence(OverlapSaveConvolver::init(
+            &response,
+            256,
+            max_response_length,
+        ));
+        check_flush_matches_silence(DirectConvolver::init(&response, 256, max_response_length));
+        check_flush_matches_silence(AutoConvolver::init(&response, 256, max_response_length));
+
+        let mut delayed = FFTConvolver::init(&response, 256, max_response_length);
+        delayed.set_latency_mode(LatencyMode::Block);
+        check_flush_matches_silence(BypassConvolver::new(delayed, 256, 256));
+
+        let mut crossfade_convolver = CrossfadeConvolver::new(
+            FFTConvolver::init(&response[..100], 256, max_response_length),
+            max_response_length,
+            256,
+            4096,
+        );
+        crossfade_convolver.update(&response);
+        check_flush_matches_silence(crossfade_convolver);
+    }
+
+    fn check_reconfigure<T: Convolution>(mut convolver: T, response: &[Sample]) {
+        let input = generate_sinusoid(6000, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+        convolver.process(&input[..1000], &mut output[..1000]);
+
+        // the response is kept
+        convolver.reconfigure(512, 5000);
+        convolver.process(&input, &mut output);
+        let expected = convolve_direct(&input, response);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+
+        // longer responses fit
+        let longer_response = generate_sinusoid(5000, 900.0, 48000.0, 0.1);
+        convolver.update(&longer_response);
+        convolver.reset();
+        for (input, output) in input.chunks(512).zip(output.chunks_mut(512)) {
+            convolver.process(input, output);
+        }
+        let expected = convolve_direct(&input, &longer_response);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn reconfigure_keeps_response() {
+        let response = generate_sinusoid(2500, 700.0, 48000.0, 0.3);
+        let max_response_length = 3000;
+        check_reconfigure(
+            FFTConvolver::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            TwoStageFFTConvolver::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            TwoStageFFTConvolver::with_split(&response, 64, 700, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            OverlapSaveConvolver::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            DirectConvolver::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            AutoConvolver::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            BypassConvolver::<FFTConvolver>::init(&response, 128, max_response_length),
+            &response,
+        );
+        check_reconfigure(
+            CrossfadeConvolver::new(
+                FFTConvolver::init(&response, 128, max_response_length),
+                max_response_length,
+                128,
+                128,
+            ),
+            &response,
+        );
+
+        // settings are carried over
+        let mut convolver = FFTConvolver::init(&response, 128, max_response_length);
+        convolver.set_update_mode(UpdateMode::PreserveHistory);
+        convolver.set_latency_mode(LatencyMode::Block);
+        convolver.reconfigure(256, 6000);
+        assert_eq!(convolver.update_mode(), UpdateMode::PreserveHistory);
+        assert_eq!(convolver.latency(), 256);
+    }
+
+    #[test]
+    fn multi_channel_convolver_matches_mono() {
+        let block_size = 64;
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(300, 2000.0, 48000.0, 0.3),
+            generate_sinusoid(1000, 500.0, 48000.0, 0.2),
+        ];
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+            generate_sinusoid(3000, 4000.0, 48000.0, 0.5),
+        ];
+
+        let process = |convolver: &mut MultiChannelConvolver| {
+            let mut outputs = vec![vec![0.0; 3000]; 3];
+            let mut processed = 0;
+            for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+                let chunk = processed..(processed + chunk_size).min(3000);
+                let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+                let mut output: Vec<&mut [Sample]> =
+                    outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+                convolver.process(&input, &mut output);
+                processed = chunk.end;
+                if processed == 3000 {
+                    break;
+                }
+            }
+            outputs
+        };
+
+        // one shared response
+        let mut convolver = MultiChannelConvolver::new(&responses[0], 3, block_size, 1000);
+        for (output, input) in process(&mut convolver).iter().zip(&inputs) {
+            let expected = convolve_direct(input, &responses[0]);
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+
+        // one response per channel
+        let response_refs: Vec<&[Sample]> = responses.iter().map(|x| x.as_slice()).collect();
+        let mut convolver =
+            MultiChannelConvolver::with_responses(&response_refs[..2], block_size, 1000);
+        convolver.update_channels(&[&responses[1], &responses[2]]);
+        let mut convolver_3 =
+            MultiChannelConvolver::with_responses(&response_refs, block_size, 1000);
+        for (i, output) in process(&mut convolver_3).iter().enumerate() {
+            let expected = convolve_direct(&inputs[i], &responses[i]);
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+        assert_eq!(convolver.response_len(1), 1000);
+        assert_eq!(convolver_3.tail_samples(), 999);
+
+        // the tails of all channels are flushed
+        let mut flushed = vec![vec![1.0; 1200]; 3];
+        let mut output: Vec<&mut [Sample]> = flushed.iter_mut().map(|x| x.as_mut_slice()).collect();
+        assert_eq!(convolver_3.flush(&mut output), 999);
+        assert_eq!(convolver_3.flush(&mut output), 0);
+        assert!(flushed
+            .iter()
+            .all(|x| x.iter().all(|sample| *sample == 0.0)));
+    }
+
+    #[test]
+    fn batched_convolver_matches_multi_channel_convolver() {
+        // processed by the fallback, and in one full and one partial batch
+        for channel_count in [3, 11] {
+            let block_size = 64;
+            let responses: Vec<Vec<Sample>> = (0..channel_count)
+                .map(|i| generate_sinusoid(100 + 90 * i, 500.0 + 300.0 * i as f32, 48000.0, 0.3))
+                .collect();
+            let response_refs: Vec<&[Sample]> = responses.iter().map(|x| x.as_slice()).collect();
+            let inputs: Vec<Vec<Sample>> = (0..channel_count)
+                .map(|i| generate_sinusoid(2000, 200.0 + 170.0 * i as f32, 48000.0, 1.0))
+                .collect();
+
+            let mut batched = BatchedConvolver::with_responses(&response_refs, block_size, 1000);
+            let mut reference =
+                MultiChannelConvolver::with_responses(&response_refs, block_size, 1000);
+            let mut output = vec![vec![0.0; 2000]; channel_count];
+            let mut expected = vec![vec![0.0; 2000]; channel_count];
+
+            let mut processed = 0;
+            for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+                let chunk = processed..(processed + chunk_size).min(2000);
+                if (1000..1100).contains(&processed) {
+                    // a linked update to a shared response
+                    batched.update(&responses[2]);
+                    reference.update_channels(&vec![responses[2].as_slice(); channel_count]);
+                }
+                let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+                let mut batched_output: Vec<&mut [Sample]> =
+                    output.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+                batched.process(&input, &mut batched_output);
+                let mut reference_output: Vec<&mut [Sample]> =
+                    expected.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+                reference.process(&input, &mut reference_output);
+                processed = chunk.end;
+                if processed == 2000 {
+                    break;
+                }
+            }
+
+            for (output, expected) in output.iter().zip(&expected) {
+                for (lhs, rhs) in output.iter().zip(expected) {
+                    assert!((lhs - rhs).abs() < 1e-3);
+                }
+            }
+            assert_eq!(batched.response_len(channel_count - 1), 280);
+
+            let mut flushed = vec![vec![1.0; 400]; channel_count];
+            assert_eq!(batched.flush(&mut flushed), 279);
+            assert_eq!(batched.flush(&mut flushed), 0);
+        }
+    }
+
+    #[test]
+    fn one_to_many_convolver_matches_mono() {
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(300, 2000.0, 48000.0, 0.3),
+            generate_sinusoid(1000, 500.0, 48000.0, 0.2),
+        ];
+        let swapped = generate_sinusoid(500, 3000.0, 48000.0, 0.3);
+        let response_refs: Vec<&[Sample]> = responses.iter().map(|x| x.as_slice()).collect();
+        let input = generate_sinusoid(3000, 1300.0, 48000.0, 1.0);
+        let mut convolver = OneToManyConvolver::new(&response_refs, 64, 1000);
+
+        let mut outputs = vec![vec![0.0; 3000]; 3];
+        let mut processed = 0;
+        for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+            let chunk = processed..(processed + chunk_size).min(3000);
+            let mut output: Vec<&mut [Sample]> =
+                outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+            convolver.process(&input[chunk.clone()], &mut output);
+            processed = chunk.end;
+            if processed == 3000 {
+                break;
+            }
+
+            // swapping the response of one output leaves the others untouched
+            if processed == 1113 {
+                convolver.update_output(1, &swapped);
+            }
+        }
+        for (output, response) in outputs.iter().zip(&responses) {
+            let expected = convolve_direct(&input, response);
+            for (lhs, rhs) in output[..1113].iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+        for (i, (output, response)) in outputs.iter().zip(&responses).enumerate() {
+            // the tail of the previous response is dropped, so the swapped response is complete
+            // from the second block boundary after the update
+            let (response, start) = if i == 1 {
+                (&swapped, 1216)
+            } else {
+                (response, 1113)
+            };
+            let expected = convolve_direct(&input, response);
+            for (lhs, rhs) in output[start..].iter().zip(&expected[start..]) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+        assert_eq!(convolver.response_len(1), 500);
+        assert_eq!(convolver.tail_samples(), 999);
+
+        let mut flushed = vec![vec![1.0; 1200]; 3];
+        assert_eq!(convolver.flush(&mut flushed), 999);
+        assert_eq!(convolver.flush(&mut flushed), 0);
+        assert!(flushed
+            .iter()
+            .all(|x| x.iter().all(|sample| *sample == 0.0)));
+    }
+
+    #[test]
+    fn matrix_convolver_sums_routes() {
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(300, 2000.0, 48000.0, 0.3),
+            generate_sinusoid(1000, 500.0, 48000.0, 0.2),
+        ];
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+        ];
+        // two inputs to three outputs, the first output only receives the second input
+        let rows: [[&[Sample]; 2]; 3] = [
+            [&[], &responses[0]],
+            [&responses[1], &responses[2]],
+            [&responses[2], &responses[0]],
+        ];
+        let rows: Vec<&[&[Sample]]> = rows.iter().map(|row| row.as_slice()).collect();
+        let mut convolver = MatrixConvolver::new(&rows, 2, 64, 1000);
+        assert_eq!(convolver.input_count(), 2);
+        assert_eq!(convolver.output_count(), 3);
+
+        let unconnected = MatrixConvolver::new(&[], 2, 64, 1000);
+        assert_eq!(unconnected.input_count(), 2);
+        assert_eq!(unconnected.output_count(), 0);
+
+        let mut outputs = vec![vec![0.0; 3000]; 3];
+        let mut processed = 0;
+        for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+            let chunk = processed..(processed + chunk_size).min(3000);
+            let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+            let mut output: Vec<&mut [Sample]> =
+                outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+            convolver.process(&input, &mut output);
+            processed = chunk.end;
+            if processed == 3000 {
+                break;
+            }
+        }
+        for (output, row) in outputs.iter().zip(&rows) {
+            let mut expected = vec![0.0; 3000];
+            for (input, response) in inputs.iter().zip(row.iter()) {
+                let convolved = convolve_direct(input, response);
+                for (sum, value) in expected.iter_mut().zip(&convolved) {
+                    *sum += value;
+                }
+            }
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+        assert_eq!(convolver.response_len(0, 0), 0);
+        assert_eq!(convolver.response_len(1, 1), 1000);
+
+        // disconnecting the longest routes shortens the tail
+        convolver.update_route(1, 1, &[]);
+        convolver.update_route(0, 2, &[]);
+        assert_eq!(convolver.tail_samples(), 699);
+        let mut flushed = vec![vec![1.0; 1200]; 3];
+        assert_eq!(convolver.flush(&mut flushed), 699);
+        assert_eq!(convolver.flush(&mut flushed), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "one response per input in every row")]
+    fn matrix_convolver_rejects_rows_of_different_length() {
+        let response = [1.0; 16];
+        MatrixConvolver::new(&[&[&response, &response], &[&response]], 2, 64, 1000);
+    }
+
+    #[test]
+    fn matrix_convolver_update_route_keeps_other_routes() {
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(300, 2000.0, 48000.0, 0.3),
+            generate_sinusoid(1000, 500.0, 48000.0, 0.2),
+        ];
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+        ];
+        let mut convolver = MatrixConvolver::new(&[&[&responses[0], &responses[1]]], 2, 64, 1000);
+
+        let mut output = vec![0.0; 3000];
+        let mut processed = 0;
+        for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+            let chunk = processed..(processed + chunk_size).min(3000);
+            let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+            convolver.process(&input, &mut [&mut output[chunk.clone()]]);
+            processed = chunk.end;
+            if processed == 3000 {
+                break;
+            }
+            if processed == 1113 {
+                // within a block
+                convolver.update_route(1, 0, &responses[2]);
+            }
+        }
+
+        // the first route continues seamlessly, the updated one applies to the earlier input
+        let kept = convolve_direct(&inputs[0], &responses[0]);
+        let before = convolve_direct(&inputs[1], &responses[1]);
+        let after = convolve_direct(&inputs[1], &responses[2]);
+        for (i, sample) in output.iter().enumerate() {
+            let updated = if i < 1113 { before[i] } else { after[i] };
+            assert!((sample - (kept[i] + updated)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn true_stereo_convolver_crossfades_linked() {
+        let responses: Vec<Vec<Sample>> = [1000.0, 2000.0, 500.0, 3000.0]
+            .iter()
+            .map(|frequency| generate_sinusoid(600, *frequency, 48000.0, 0.2))
+            .collect();
+        let new_responses: Vec<Vec<Sample>> = [700.0, 1500.0, 2500.0, 400.0]
+            .iter()
+            .map(|frequency| generate_sinusoid(900, *frequency, 48000.0, 0.2))
+            .collect();
+        let last_responses: Vec<Vec<Sample>> = [900.0, 1200.0, 600.0, 1800.0]
+            .iter()
+            .map(|frequency| generate_sinusoid(800, *frequency, 48000.0, 0.2))
+            .collect();
+        fn set(responses: &[Vec<Sample>]) -> [&[Sample]; 4] {
+            std::array::from_fn(|i| responses[i].as_slice())
+        }
+        let inputs = [
+            generate_sinusoid(6000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(6000, 300.0, 48000.0, 0.8),
+        ];
+        let expected = |responses: &[Vec<Sample>]| {
+            let [ll, lr, rl, rr] = set(responses);
+            let mix = |a: Vec<Sample>, b: Vec<Sample>| -> Vec<Sample> {
+                a.iter().zip(&b).map(|(a, b)| a + b).collect()
+            };
+            [
+                mix(
+                    convolve_direct(&inputs[0], ll),
+                    convolve_direct(&inputs[1], rl),
+                ),
+                mix(
+                    convolve_direct(&inputs[0], lr),
+                    convolve_direct(&inputs[1], rr),
+                ),
+            ]
+        };
+
+        let crossfade_samples = 500;
+        let mut convolver =
+            TrueStereoConvolver::new(set(&responses), 64, 1000, 256, crossfade_samples);
+        let mut outputs = vec![vec![0.0; 6000]; 2];
+        let mut processed = 0;
+        for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+            let chunk = processed..(processed + chunk_size).min(6000);
+            let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+            let mut output: Vec<&mut [Sample]> =
+                outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+            convolver.process(&input, &mut output);
+            processed = chunk.end;
+            if processed == 6000 {
+                break;
+            }
+            if processed == 2055 {
+                convolver.update(set(&new_responses));
+                assert!(convolver.is_crossfading());
+            }
+            if processed == 2355 {
+                // pending until the running fade has finished
+                convolver.update(set(&last_responses));
+                assert_eq!(convolver.response_len(), 900);
+            }
+        }
+
+        // both channels fade at once, the pending set right after the first fade
+        let fade_end = 2055 + 2 * crossfade_samples;
+        let (before, after) = (expected(&responses), expected(&last_responses));
+        for channel in 0..2 {
+            for i in 0..2055 {
+                assert!((outputs[channel][i] - before[channel][i]).abs() < 1e-3);
+            }
+            for i in fade_end..6000 {
+                assert!((outputs[channel][i] - after[channel][i]).abs() < 1e-3);
+            }
+        }
+        assert!(!convolver.is_crossfading());
+        assert_eq!(convolver.response_len(), 800);
+        assert_eq!(convolver.tail_samples(), 799);
+
+        let mut flushed = vec![vec![1.0; 1000]; 2];
+        assert_eq!(convolver.flush(&mut flushed), 799);
+        assert_eq!(convolver.flush(&mut flushed), 0);
+
+        convolver.reset();
+        assert_eq!(convolver.flush(&mut flushed), 0);
+        assert!(flushed
+            .iter()
+            .all(|x| x.iter().all(|sample| *sample == 0.0)));
+    }
+
+    #[test]
+    fn planar_convolution_of_mixed_convolvers() {
+        use crate::PlanarConvolution;
+
+        let responses: Vec<Vec<Sample>> = [1000.0, 2000.0, 500.0, 3000.0]
+            .iter()
+            .map(|frequency| generate_sinusoid(600, *frequency, 48000.0, 0.2))
+            .collect();
+        let [ll, lr, rl, rr]: [&[Sample]; 4] = std::array::from_fn(|i| responses[i].as_slice());
+        let mut convolvers: Vec<Box<dyn PlanarConvolution>> = vec![
+            Box::new(TrueStereoConvolver::new(
+                [ll, lr, rl, rr],
+                64,
+                1000,
+                256,
+                500,
+            )),
+            Box::new(MatrixConvolver::new(&[&[ll, rl], &[lr, rr]], 2, 64, 1000)),
+        ];
+        let mut clones = convolvers.clone();
+
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+        ];
+        let input: Vec<&[Sample]> = inputs.iter().map(|x| x.as_slice()).collect();
+        let expected: Vec<Vec<Sample>> = [[ll, rl], [lr, rr]]
+            .iter()
+            .map(|row| {
+                let left = convolve_direct(&inputs[0], row[0]);
+                let right = convolve_direct(&inputs[1], row[1]);
+                left.iter().zip(&right).map(|(a, b)| a + b).collect()
+            })
+            .collect();
+        for convolver in convolvers.iter_mut().chain(clones.iter_mut()) {
+            assert_eq!(convolver.input_count(), 2);
+            assert_eq!(convolver.output_count(), 2);
+            let mut outputs = vec![vec![0.0; 3000]; 2];
+            let mut output: Vec<&mut [Sample]> =
+                outputs.iter_mut().map(|x| x.as_mut_slice()).collect();
+            convolver.process(&input, &mut output);
+            for (output, expected) in outputs.iter().zip(&expected) {
+                for (lhs, rhs) in output.iter().zip(expected) {
+                    assert!((lhs - rhs).abs() < 1e-3);
+                }
+            }
+            assert_eq!(convolver.tail_samples(), 599);
+            let mut flushed = vec![vec![0.0; 1000]; 2];
+            let mut flushed: Vec<&mut [Sample]> =
+                flushed.iter_mut().map(|x| x.as_mut_slice()).collect();
+            assert_eq!(convolver.flush(&mut flushed), 599);
+        }
+    }
+
+    #[test]
+    fn convolution_reverb_mixes_wet_and_dry() {
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(500, 2000.0, 48000.0, 0.3),
+        ];
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+        ];
+        let config = ConvolverConfig::builder()
+            .sample_rate(48000.0)
+            .block_size(64)
+            .max_response_length(1000)
+            .crossfade_samples(32)
+            .build()
+            .unwrap();
+        let mut reverb =
+            ConvolutionReverb::<FFTConvolver>::new([&responses[0], &responses[1]], &config, 500)
+                .unwrap();
+
+        let process = |reverb: &mut ConvolutionReverb<FFTConvolver>| {
+            let mut outputs = vec![vec![0.0; 3000]; 2];
+            let mut processed = 0;
+            for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+                let chunk = processed..(processed + chunk_size).min(3000);
+                let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+                let mut output: Vec<&mut [Sample]> =
+                    outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+                reverb.process(&input, &mut output);
+                processed = chunk.end;
+                if processed == 3000 {
+                    break;
+                }
+            }
+            outputs
+        };
+
+        // fully wet, the pre-delay shifts the responses
+        reverb.set_pre_delay(300);
+        let outputs = process(&mut reverb);
+        for ((output, input), response) in outputs.iter().zip(&inputs).zip(&responses) {
+            let mut delayed_response = vec![0.0; 300];
+            delayed_response.extend_from_slice(response);
+            let expected = convolve_direct(input, &delayed_response);
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+        assert_eq!(reverb.tail_samples(), 999);
+
+        // dry input on top of a mono wet output
+        reverb.set_dry_gain(1.0);
+        reverb.set_width(0.0);
+        reverb.reset();
+        let outputs = process(&mut reverb);
+        for i in 0..3000 {
+            let wet_left = outputs[0][i] - inputs[0][i];
+            let wet_right = outputs[1][i] - inputs[1][i];
+            assert!((wet_left - wet_right).abs() < 1e-3);
+        }
+
+        let mut flushed = vec![vec![1.0; 1200]; 2];
+        assert_eq!(reverb.flush(&mut flushed), 999);
+        assert_eq!(reverb.flush(&mut flushed), 0);
+    }
+
+    #[test]
+    fn convolution_reverb_changes_pre_delay_while_playing() {
+        let responses = [
+            generate_sinusoid(700, 1000.0, 48000.0, 0.5),
+            generate_sinusoid(500, 2000.0, 48000.0, 0.3),
+        ];
+        let inputs = [
+            generate_sinusoid(3000, 1300.0, 48000.0, 1.0),
+            generate_sinusoid(3000, 300.0, 48000.0, 0.8),
+        ];
+        let config = ConvolverConfig::builder()
+            .sample_rate(48000.0)
+            .block_size(64)
+            .max_response_length(1000)
+            .crossfade_samples(32)
+            .build()
+            .unwrap();
+        let mut reverb = ConvolutionReverb::<CrossfadeConvolver<FFTConvolver>>::new(
+            [&responses[0], &responses[1]],
+            &config,
+            500,
+        )
+        .unwrap();
+
+        let mut outputs = vec![vec![0.0; 3000]; 2];
+        let mut processed = 0;
+        for chunk_size in [100, 64, 7, 300].into_iter().cycle() {
+            let chunk = processed..(processed + chunk_size).min(3000);
+            let input: Vec<&[Sample]> = inputs.iter().map(|x| &x[chunk.clone()]).collect();
+            let mut output: Vec<&mut [Sample]> =
+                outputs.iter_mut().map(|x| &mut x[chunk.clone()]).collect();
+            reverb.process(&input, &mut output);
+            processed = chunk.end;
+            if processed == 3000 {
+                break;
+            }
+            if processed == 1113 {
+                reverb.set_pre_delay(300);
+            }
+        }
+
+        // once the crossfade has finished, the earlier input rings out behind the new delay
+        for ((output, input), response) in outputs.iter().zip(&inputs).zip(&responses) {
+            let before = convolve_direct(input, response);
+            let mut delayed_response = vec![0.0; 300];
+            delayed_response.extend_from_slice(response);
+            let after = convolve_direct(input, &delayed_response);
+            for i in 0..1113 {
+                assert!((output[i] - before[i]).abs() < 1e-3);
+            }
+            for i in 1300..3000 {
+                assert!((output[i] - after[i]).abs() < 1e-3);
+            }
+        }
+        assert_eq!(reverb.tail_samples(), 999);
+    }
+
+    #[test]
+    fn two_stage_convolver_splits_early_and_late() {
+        let response = generate_sinusoid(6000, 700.0, 48000.0, 0.1);
+        let input = generate_sinusoid(10000, 1300.0, 48000.0, 1.0);
+        let config = ConvolverConfig::builder()
+            .sample_rate(48000.0)
+            .block_size(64)
+            .max_response_length(6000)
+            .split_seconds(0.03)
+            .build()
+            .unwrap();
+        let mut convolver: TwoStageFFTConvolver = config.build(&response).unwrap();
+        assert_eq!(convolver.split(), 1440);
+
+        let mut early = vec![0.0; input.len()];
+        let mut late = vec![0.0; input.len()];
+        let chunks = input
+            .chunks(100)
+            .zip(early.chunks_mut(100))
+            .zip(late.chunks_mut(100));
+        for ((input, early), late) in chunks {
+            convolver.process_split(input, early, late);
+        }
+
+        let expected_early = convolve_direct(&input, &response[..1440]);
+        let mut late_response = vec![0.0; 1440];
+        late_response.extend_from_slice(&response[1440..]);
+        let expected_late = convolve_direct(&input, &late_response);
+        for (lhs, rhs) in early.iter().zip(&expected_early) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+        for (lhs, rhs) in late.iter().zip(&expected_late) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+
+        // updates keep the split
+        let short_response = generate_sinusoid(2000, 900.0, 48000.0, 0.2);
+        convolver.update(&short_response);
+        convolver.reset();
+        convolver.process_split(&input, &mut early, &mut late);
+        let expected_late = convolve_direct(&input, &{
+            let mut late_response = vec![0.0; 1440];
+            late_response.extend_from_slice(&short_response[1440..]);
+            late_response
+        });
+        for (lhs, rhs) in late.iter().zip(&expected_late) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+
+        assert_eq!(
+            ConvolverConfig::builder()
+                .block_size(64)
+                .max_response_length(6000)
+                .split_point(32)
+                .build(),
+            Err(ConfigError::SplitPointTooSmall {
+                split_point: 32,
+                block_size: 64
+            })
+        );
+    }
+
+    fn check_scheduled_update<T: Convolution>(
+        mut convolver: T,
+        schedule_update: impl Fn(&mut T, &[Sample], usize),
+    ) {
+        let response = generate_sinusoid(500, 900.0, 48000.0, 0.3);
+        let input = generate_sinusoid(4000, 1300.0, 48000.0, 1.0);
+        // a long enough crossfade is still running when the scheduled update is due
+        convolver.update(&generate_sinusoid(300, 600.0, 48000.0, 0.4));
+        let initial = convolver.clone();
+
+        // reference with the update between two buffers
+        let mut expected = vec![0.0; input.len()];
+        convolver.process(&input[..1234], &mut expected[..1234]);
+        convolver.update(&response);
+        convolver.process(&input[1234..], &mut expected[1234..]);
+
+        for buffer_size in [1, 64, 100, 333, 4000] {
+            let mut convolver = initial.clone();
+            schedule_update(&mut convolver, &response, 1234);
+            let mut output = vec![0.0; input.len()];
+            for (input, output) in input
+                .chunks(buffer_size)
+                .zip(output.chunks_mut(buffer_size))
+            {
+                convolver.process(input, output);
+            }
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn scheduled_update_is_sample_accurate() {
+        let response = generate_sinusoid(700, 1000.0, 48000.0, 0.5);
+        check_scheduled_update(
+            FFTConvolver::init(&response, 64, 1000),
+            FFTConvolver::schedule_update,
+        );
+        check_scheduled_update(
+            CrossfadeConvolver::new(FFTConvolver::init(&response, 64, 1000), 1000, 128, 256),
+            CrossfadeConvolver::schedule_update,
+        );
+        check_scheduled_update(
+            CrossfadeConvolver::new(FFTConvolver::init(&response, 64, 1000), 1000, 128, 2000),
+            CrossfadeConvolver::schedule_update,
+        );
+
+        // reset cancels a scheduled update
+        let mut convolver = FFTConvolver::init(&response, 64, 1000);
+        convolver.schedule_update(&[], 100);
+        assert!(convolver.is_update_scheduled());
+        convolver.reset();
+        assert!(!convolver.is_update_scheduled());
+        let mut output = vec![0.0; 200];
+        convolver.process(&[1.0; 200], &mut output);
+        assert_eq!(convolver.response_len(), 700);
+    }
+
+    #[test]
+    fn interleaved_convolver_converts_formats() {
+        let responses = [
+            // quiet enough not to clip
+            generate_sinusoid(300, 1000.0, 48000.0, 0.005),
+            generate_sinusoid(500, 2000.0, 48000.0, 0.003),
+        ];
+        let response_refs: Vec<&[Sample]> = responses.iter().map(|x| x.as_slice()).collect();
+        let inputs = [
+            generate_sinusoid(2000, 1300.0, 48000.0, 0.5),
+            generate_sinusoid(2000, 300.0, 48000.0, 0.25),
+        ];
+        let expected: Vec<Vec<Sample>> = inputs
+            .iter()
+            .zip(&responses)
+            .map(|(input, response)| convolve_direct(input, response))
+            .collect();
+        let convolver = MultiChannelConvolver::with_responses(&response_refs, 64, 500);
+
+        // interleaved i16, in buffers larger than the scratch
+        let mut interleaved = InterleavedConvolver::new(convolver.clone(), 100);
+        let input: Vec<i16> = (0..2000)
+            .flat_map(|i| {
+                [
+                    i16::from_sample(inputs[0][i]),
+                    i16::from_sample(inputs[1][i]),
+                ]
+            })
+            .collect();
+        let mut output = vec![0i16; input.len()];
+        for (input, output) in input.chunks(2 * 333).zip(output.chunks_mut(2 * 333)) {
+            interleaved.process(input, output);
+        }
+        for (frame, output) in output.chunks(2).enumerate() {
+            for channel in 0..2 {
+                assert!((output[channel].to_sample() - expected[channel][frame]).abs() < 1e-3);
+            }
+        }
+
+        // planar packed 24 bit
+        let mut planar = InterleavedConvolver::new(convolver, 100);
+        let input: Vec<Vec<I24>> = inputs
+            .iter()
+            .map(|input| input.iter().map(|x| I24::from_sample(*x)).collect())
+            .collect();
+        let mut output = vec![vec![I24::default(); 2000]; 2];
+        let input_refs: Vec<&[I24]> = input.iter().map(|x| x.as_slice()).collect();
+        let mut output_refs: Vec<&mut [I24]> =
+            output.iter_mut().map(|x| x.as_mut_slice()).collect();
+        planar.process_planar(&input_refs, &mut output_refs);
+        for (output, expected) in output.iter().zip(&expected) {
+            for (lhs, rhs) in output.iter().zip(expected) {
+                assert!((lhs.to_sample() - rhs).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn dyn_convolution_of_mixed_engines() {
+        use crate::DynConvolution;
+
+        let response = generate_sinusoid(3000, 700.0, 48000.0, 0.3);
+        let mut convolvers: Vec<Box<dyn DynConvolution>> = vec![
+            Box::new(FFTConvolver::init(&response, 256, response.len())),
+            Box::new(TwoStageFFTConvolver::init(&response, 256, response.len())),
+            Box::new(CrossfadeConvolver::new(
+                FFTConvolver::init(&response, 256, response.len()),
+                response.len(),
+                256,
+                256,
+            )),
+        ];
+        let mut clones = convolvers.clone();
+
+        // with both traits in scope, calls on a concrete convolver resolve to `Convolution`
+        let mut concrete = FFTConvolver::init(&response, 256, response.len());
+        assert_eq!(concrete.latency(), 0);
+        concrete.reset();
+
+        let input = generate_sinusoid(4096, 1300.0, 48000.0, 1.0);
+        let expected = convolve_direct(&input, &response);
+        let mut output = vec![0.0; input.len()];
+        for convolver in convolvers.iter_mut().chain(clones.iter_mut()) {
+            assert_eq!(convolver.dyn_response_len(), response.len());
+            assert_eq!(convolver.dyn_latency(), 0);
+            convolver.dyn_process(&input, &mut output);
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn build_convolvers_from_config() {
+        let block_size = 128;
+        let response = generate_sinusoid(3000, 700.0, 48000.0, 0.3);
+        let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+        let expected = convolve_direct(&input, &response);
+
+        let config = ConvolverConfig::builder()
+            .block_size(block_size)
+            .max_response_length(4096)
+            .latency_mode(LatencyMode::Block)
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.build::<TwoStageFFTConvolver>(&response).err(),
+            Some(ConfigError::UnsupportedLatencyMode(LatencyMode::Block))
+        );
+
+        let mut convolver: FFTConvolver = config.build(&response).unwrap();
+        assert_eq!(convolver.latency(), block_size);
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(100).zip(output.chunks_mut(100)) {
+            convolver.process(input, output);
+        }
+        assert!(output[..block_size].iter().all(|sample| *sample == 0.0));
+        for (lhs, rhs) in output[block_size..].iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+
+        let config = ConvolverConfig::builder()
+            .block_size(64)
+            .max_response_length(4096)
+            .build()
+            .unwrap();
+        // the block size sets the head like with `init`, keeping the default ratio to the tail
+        let two_stage: TwoStageFFTConvolver = config.build(&response).unwrap();
+        assert_eq!(two_stage.split(), 512);
+        assert_eq!(
+            TwoStageFFTConvolver::init(&response, 64, 4096).split(),
+            two_stage.split()
+        );
+
+        let mut convolver: CrossfadeConvolver<TwoStageFFTConvolver> =
+            config.build(&response).unwrap();
+        assert_eq!(convolver.latency(), 0);
+        convolver.process(&input, &mut output);
+        for (lhs, rhs) in output.iter().zip(&expected) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn build_convolvers_with_fft_backend() {
+        fn check<T: Convolution>(config: &ConvolverConfig, response: &[Sample]) -> T {
+            let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+            let expected = convolve_direct(&input, response);
+            let mut convolver: T = config.build(response).unwrap();
+            let mut output = vec![0.0; input.len()];
+            for (input, output) in input.chunks(100).zip(output.chunks_mut(100)) {
+                convolver.process(input, output);
+            }
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+            convolver
+        }
+
+        let response = generate_sinusoid(3000, 700.0, 48000.0, 0.3);
+        let config = ConvolverConfig::builder()
+            .block_size(128)
+            .max_response_length(4096)
+            .fft_backend(FftBackend::RustFft)
+            .build()
+            .unwrap();
+
+        let mut convolver: FFTConvolver = check(&config, &response);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+        convolver.reconfigure(256, 4096);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+
+        let mut convolver: TwoStageFFTConvolver = check(&config, &response);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+        convolver.reconfigure(256, 4096);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+
+        let mut convolver: OverlapSaveConvolver = check(&config, &response);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+        convolver.reconfigure(256, 4096);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+
+        let mut convolver: AutoConvolver = check(&config, &response);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+        convolver.reconfigure(256, 4096);
+        assert_eq!(convolver.fft_backend(), FftBackend::RustFft);
+
+        let convolver: FFTConvolver = FFTConvolver::init(&response, 128, 4096);
+        assert_eq!(convolver.fft_backend(), FftBackend::RealFft);
+    }
+
+    #[test]
+    fn auto_convolver_switches_engines() {
+        let block_size = 64;
+        let responses = [
+            (generate_sinusoid(32, 700.0, 48000.0, 0.5), Engine::Direct),
+            (
+                generate_sinusoid(20000, 500.0, 48000.0, 0.1),
+                Engine::TwoStage,
+            ),
+            (
+                generate_sinusoid(1000, 900.0, 48000.0, 0.2),
+                Engine::Uniform,
+            ),
+            (generate_sinusoid(16, 300.0, 48000.0, 0.5), Engine::Direct),
+        ];
+        let mut convolver = AutoConvolver::init(&responses[0].0, block_size, 20000);
+        let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+        let mut output = vec![0.0; input.len()];
+
+        for (response, engine) in &responses {
+            // a switched engine starts from silence
+            convolver.update(response);
+            assert_eq!(convolver.engine(), *engine);
+            assert_eq!(convolver.response_len(), response.len());
+            for (input, output) in input.chunks(block_size).zip(output.chunks_mut(block_size)) {
+                convolver.process(input, output);
+            }
+            let expected = convolve_direct(&input, response);
+            for (lhs, rhs) in output.iter().zip(&expected) {
+                assert!((lhs - rhs).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn overlap_save_convolver_matches_direct_convolver() {
+        let block_size = 128;
+        let response_a = generate_sinusoid(1000, 700.0, 48000.0, 0.3);
+        let response_b = generate_sinusoid(600, 1100.0, 48000.0, 0.4);
+        let input = generate_sinusoid(8192, 1300.0, 48000.0, 1.0);
+
+        let mut convolver = OverlapSaveConvolver::init(&response_a, block_size, 1000);
+        let mut reference = DirectConvolver::init(&response_a, block_size, 1000);
+        let mut output = vec![0.0; input.len()];
+        let mut output_reference = vec![0.0; input.len()];
+
+        let mut processed = 0;
+        for (i, buffer_size) in [100, 128, 7, 300, 1].into_iter().cycle().enumerate() {
+            // both swap the response for the whole history
+            if i == 10 {
+                convolver.update(&response_b);
+                reference.update(&response_b);
+            }
+            let end = (processed + buffer_size).min(input.len());
+            convolver.process(&input[processed..end], &mut output[processed..end]);
+            reference.process(
+                &input[processed..end],
+                &mut output_reference[processed..end],
+            );
+            processed = end;
+            if processed == input.len() {
+                break;
+            }
+        }
+
+        for (lhs, rhs) in output.iter().zip(&output_reference) {
+            assert!((lhs - rhs).abs() < 1e-3);
+        }
+    }
 }
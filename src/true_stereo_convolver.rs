@@ -0,0 +1,299 @@
+use crate::crossfade_convolver::{Crossfader, FadingState, RaisedCosineMixer, Target};
+use crate::matrix_convolver::MatrixConvolver;
+use crate::{PlanarConvolution, Sample, TailFlush, SILENCE};
+
+/// Stereo convolution with a true-stereo response set, e.g. from a reverb capture.
+///
+/// The four responses are given in the order LL, LR, RL, RR, where LR is the response from the
+/// left input to the right output. Both inputs are transformed once and shared by all four
+/// routes. Updates are linked and crossfaded: the new set is convolved next to the previous one
+/// from the same input spectra and both outputs fade at once, so the channels never run out of
+/// sync. A response set that arrives during a crossfade starts its fade on the sample after the
+/// running one has finished.
+#[derive(Clone)]
+pub struct TrueStereoConvolver {
+    // outputs 0 and 1 are left and right of set A, outputs 2 and 3 those of set B
+    matrix: MatrixConvolver,
+    crossfader: Crossfader<RaisedCosineMixer>,
+    buffers: [Vec<Sample>; 4],
+    // the set that was faded out still needs to be disconnected
+    inactive_connected: bool,
+    stored_responses: [Vec<Sample>; 4],
+    stored_lens: [usize; 4],
+    response_pending: bool,
+    tail_flush: TailFlush,
+}
+
+impl TrueStereoConvolver {
+    pub fn new(
+        responses: [&[Sample]; 4],
+        block_size: usize,
+        max_response_length: usize,
+        max_buffer_size: usize,
+        crossfade_samples: usize,
+    ) -> Self {
+        assert!(
+            max_buffer_size > 0,
+            "max_buffer_size must be greater than 0"
+        );
+        let [ll, lr, rl, rr] = responses;
+        let matrix = MatrixConvolver::new(
+            &[&[ll, rl], &[lr, rr], &[&[], &[]], &[&[], &[]]],
+            2,
+            block_size,
+            max_response_length,
+        );
+        // the matrix convolves a new set with the earlier input as well, so it can be faded in
+        // right away
+        Self {
+            matrix,
+            crossfader: Crossfader::new(RaisedCosineMixer, crossfade_samples, 0),
+            buffers: std::array::from_fn(|_| vec![0.0; max_buffer_size]),
+            inactive_connected: false,
+            stored_responses: std::array::from_fn(|_| vec![0.0; max_response_length]),
+            stored_lens: [0; 4],
+            response_pending: false,
+            tail_flush: TailFlush::default(),
+        }
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        match self.crossfader.fading_state {
+            FadingState::Approaching(_) => true,
+            FadingState::Reached(_) => false,
+        }
+    }
+
+    /// Crossfades into a new response set in the order LL, LR, RL, RR. Real-time safe.
+    pub fn update(&mut self, responses: [&[Sample]; 4]) {
+        if !self.is_crossfading() {
+            self.swap(responses);
+            self.response_pending = false;
+            return;
+        }
+
+        for ((stored, stored_len), response) in self
+            .stored_responses
+            .iter_mut()
+            .zip(&mut self.stored_lens)
+            .zip(responses)
+        {
+            assert!(response.len() <= stored.len());
+            stored[..response.len()].copy_from_slice(response);
+            *stored_len = response.len();
+        }
+        self.response_pending = true;
+    }
+
+    /// Convolves a stereo pair of buffers, given as two input and two output slices of the same
+    /// length.
+    pub fn process<I: AsRef<[Sample]>, O: AsMut<[Sample]>>(
+        &mut self,
+        input: &[I],
+        output: &mut [O],
+    ) {
+        assert_eq!(input.len(), 2, "two input slices");
+        assert_eq!(output.len(), 2, "two output slices");
+        let [left, right] = output else {
+            unreachable!()
+        };
+        let (input_left, input_right) = (input[0].as_ref(), input[1].as_ref());
+        let (output_left, output_right) = (left.as_mut(), right.as_mut());
+        let len = input_left.len();
+        assert!(
+            input_right.len() == len && output_left.len() == len && output_right.len() == len,
+            "all buffers must have the same length"
+        );
+        self.tail_flush.restart();
+
+        // buffers larger than max_buffer_size are processed in several chunks
+        let max_buffer_size = self.buffers[0].len();
+        let mut processed = 0;
+        while processed < len {
+            let chunk = processed..processed + self.chunk_len(len - processed, max_buffer_size);
+            self.process_chunk(
+                [&input_left[chunk.clone()], &input_right[chunk.clone()]],
+                [
+                    &mut output_left[chunk.clone()],
+                    &mut output_right[chunk.clone()],
+                ],
+            );
+            processed = chunk.end;
+        }
+    }
+
+    pub fn latency(&self) -> usize {
+        0
+    }
+
+    /// Length of the longest response of the set that is currently applied.
+    pub fn response_len(&self) -> usize {
+        let first_output = self.first_output(self.crossfader.fading_state.target());
+        (first_output..first_output + 2)
+            .flat_map(|output| (0..2).map(move |input| (input, output)))
+            .map(|(input, output)| self.matrix.response_len(input, output))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn tail_samples(&self) -> usize {
+        self.matrix.tail_samples()
+    }
+
+    /// Clears all processing history and completes a crossfade in progress, a pending response
+    /// set is dropped. Real-time safe.
+    pub fn reset(&mut self) {
+        self.matrix.reset();
+        self.crossfader.reset();
+        self.response_pending = false;
+        self.tail_flush = TailFlush::default();
+    }
+
+    /// Writes the remaining tail of both outputs like `Convolution::flush`.
+    pub fn flush<O: AsMut<[Sample]>>(&mut self, output: &mut [O]) -> usize {
+        assert_eq!(output.len(), 2, "two output slices");
+        let [left, right] = output else {
+            unreachable!()
+        };
+        let (output_left, output_right) = (left.as_mut(), right.as_mut());
+        let len = output_left.len();
+        assert_eq!(
+            output_right.len(),
+            len,
+            "all buffers must have the same length"
+        );
+
+        let written = std::cmp::min(self.tail_flush.remaining(self.tail_samples()), len);
+        let max_chunk = std::cmp::min(SILENCE.len(), self.buffers[0].len());
+        let mut processed = 0;
+        while processed < written {
+            let chunk = processed..processed + self.chunk_len(written - processed, max_chunk);
+            let silence = &SILENCE[..chunk.len()];
+            self.process_chunk(
+                [silence, silence],
+                [
+                    &mut output_left[chunk.clone()],
+                    &mut output_right[chunk.clone()],
+                ],
+            );
+            processed = chunk.end;
+        }
+        output_left[written..].fill(0.);
+        output_right[written..].fill(0.);
+        self.tail_flush.advance(written);
+        written
+    }
+
+    // Length of the next chunk of at most `max_chunk` samples, a pending set starts its fade on
+    // the sample after the running one has finished
+    fn chunk_len(&self, remaining: usize, max_chunk: usize) -> usize {
+        let len = std::cmp::min(remaining, max_chunk);
+        if self.response_pending && self.is_crossfading() {
+            std::cmp::min(len, self.crossfader.remaining_samples())
+        } else {
+            len
+        }
+    }
+
+    // index of the left output of the set that `target` refers to
+    fn first_output(&self, target: Target) -> usize {
+        match target {
+            Target::A => 0,
+            Target::B => 2,
+        }
+    }
+
+    fn inactive_target(&self) -> Target {
+        match self.crossfader.fading_state.target() {
+            Target::A => Target::B,
+            Target::B => Target::A,
+        }
+    }
+
+    // Applies the responses to the routes of the set that `target` refers to
+    fn connect(&mut self, target: Target, responses: [&[Sample]; 4]) {
+        let [ll, lr, rl, rr] = responses;
+        let first_output = self.first_output(target);
+        self.matrix.update_route(0, first_output, ll);
+        self.matrix.update_route(1, first_output, rl);
+        self.matrix.update_route(0, first_output + 1, lr);
+        self.matrix.update_route(1, first_output + 1, rr);
+    }
+
+    // Loads the responses into the inactive set and fades into it
+    fn swap(&mut self, responses: [&[Sample]; 4]) {
+        let target = self.inactive_target();
+        self.connect(target, responses);
+        self.crossfader.fade_into(target);
+        self.inactive_connected = true;
+    }
+
+    fn process_chunk(&mut self, input: [&[Sample]; 2], output: [&mut [Sample]; 2]) {
+        if !self.is_crossfading() && self.response_pending {
+            // taken out while the matrix is borrowed, which does not allocate
+            let stored_responses = std::mem::take(&mut self.stored_responses);
+            let [ll, lr, rl, rr] = &stored_responses;
+            let [ll_len, lr_len, rl_len, rr_len] = self.stored_lens;
+            self.swap([&ll[..ll_len], &lr[..lr_len], &rl[..rl_len], &rr[..rr_len]]);
+            self.stored_responses = stored_responses;
+            self.response_pending = false;
+        }
+
+        let len = input[0].len();
+        let [a_left, a_right, b_left, b_right] = &mut self.buffers;
+        self.matrix.process(
+            &input,
+            &mut [
+                &mut a_left[..len],
+                &mut a_right[..len],
+                &mut b_left[..len],
+                &mut b_right[..len],
+            ],
+        );
+
+        let [output_left, output_right] = output;
+        for (i, (left, right)) in output_left.iter_mut().zip(output_right).enumerate() {
+            // the gains of the raised cosine sum up to one, so the gain of set A is applied
+            // to both channels
+            let gain = self.crossfader.mix(1.0, 0.0);
+            *left = a_left[i] * gain + b_left[i] * (1.0 - gain);
+            *right = a_right[i] * gain + b_right[i] * (1.0 - gain);
+        }
+
+        // the faded out set no longer costs any processing
+        if self.inactive_connected && !self.is_crossfading() {
+            self.connect(self.inactive_target(), [&[]; 4]);
+            self.inactive_connected = false;
+        }
+    }
+}
+
+impl PlanarConvolution for TrueStereoConvolver {
+    fn input_count(&self) -> usize {
+        2
+    }
+
+    fn output_count(&self) -> usize {
+        2
+    }
+
+    fn process(&mut self, input: &[&[Sample]], output: &mut [&mut [Sample]]) {
+        TrueStereoConvolver::process(self, input, output);
+    }
+
+    fn tail_samples(&self) -> usize {
+        TrueStereoConvolver::tail_samples(self)
+    }
+
+    fn reset(&mut self) {
+        TrueStereoConvolver::reset(self);
+    }
+
+    fn flush(&mut self, output: &mut [&mut [Sample]]) -> usize {
+        TrueStereoConvolver::flush(self, output)
+    }
+
+    fn clone_box(&self) -> Box<dyn PlanarConvolution> {
+        Box::new(self.clone())
+    }
+}